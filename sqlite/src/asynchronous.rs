@@ -0,0 +1,464 @@
+//! 异步接口（需要启用 `async` 特性）。
+//!
+//! `AsyncMovieManager` 与 `PooledMovieManager` 的同步方法一一对应（`MovieStore`、别名标题、
+//! 自然键、导演、dump/load、变更记录、事务和试运行），每个调用都通过
+//! `tokio::task::spawn_blocking` 在阻塞线程池上执行，不会占用异步执行器。
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::task;
+
+#[cfg(feature = "toml")]
+use crate::Dump;
+use crate::{
+    AlternateTitle, ChangeEvent, ChangeSet, Director, Error, Field, FuzzyMatch, Movie, MovieFilter,
+    MovieManager, MovieRecord, MovieStore, PoolConfig, PooledMovieManager, Result, TitleKind,
+    UpsertOutcome,
+};
+
+/// 面向 tokio 的异步电影管理器，可在任务之间克隆共享。
+#[derive(Clone)]
+pub struct AsyncMovieManager {
+    pool: PooledMovieManager,
+}
+
+impl AsyncMovieManager {
+    /// 使用默认连接池配置打开数据库。
+    pub async fn new(db_path: &str) -> Result<Self> {
+        Self::with_config(db_path, PoolConfig::default()).await
+    }
+
+    /// 使用指定连接池配置打开数据库。
+    pub async fn with_config(db_path: &str, config: PoolConfig) -> Result<Self> {
+        let db_path = db_path.to_string();
+        let pool = blocking(move || PooledMovieManager::with_config(&db_path, config)).await?;
+        Ok(Self { pool })
+    }
+
+    /// 包装一个已有的连接池。
+    pub fn from_pool(pool: PooledMovieManager) -> Self {
+        Self { pool }
+    }
+
+    /// 添加一部新的电影。
+    pub async fn create_movie(
+        &self,
+        id: u32,
+        title: String,
+        director: String,
+        release_date: String,
+    ) -> Result<()> {
+        let pool = self.pool.clone();
+        blocking(move || pool.create_movie(id, title, director, release_date)).await
+    }
+
+    /// 根据ID读取一部电影。
+    pub async fn read_movie(&self, id: u32) -> Result<Option<Movie>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.read_movie(id)).await
+    }
+
+    /// 更新一部电影，电影不存在时返回 `false`。
+    pub async fn update_movie(
+        &self,
+        id: u32,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<bool> {
+        let pool = self.pool.clone();
+        blocking(move || pool.update_movie(id, title, director, release_date)).await
+    }
+
+    /// 删除一部电影，电影不存在时返回 `false`。
+    pub async fn delete_movie(&self, id: u32) -> Result<bool> {
+        let pool = self.pool.clone();
+        blocking(move || pool.delete_movie(id)).await
+    }
+
+    /// 列出所有电影。
+    pub async fn list_movies(&self) -> Result<Vec<Movie>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.list_movies()).await
+    }
+
+    /// 搜索满足条件的电影。
+    pub async fn search_movies(&self, filter: &MovieFilter) -> Result<Vec<Movie>> {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        blocking(move || pool.search_movies(&filter)).await
+    }
+
+    /// 根据ID读取一部电影的部分字段。
+    pub async fn read_fields(&self, id: u32, fields: &[Field]) -> Result<Option<MovieRecord>> {
+        let pool = self.pool.clone();
+        let fields = fields.to_vec();
+        blocking(move || pool.read_fields(id, &fields)).await
+    }
+
+    /// 按ID顺序列出满足条件的电影的部分字段。
+    pub async fn search_fields(
+        &self,
+        filter: &MovieFilter,
+        fields: &[Field],
+    ) -> Result<Vec<MovieRecord>> {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        let fields = fields.to_vec();
+        blocking(move || pool.search_fields(&filter, &fields)).await
+    }
+
+    /// 按相似度列出最接近 `query` 的电影，最多 `limit` 部。
+    pub async fn fuzzy_search(&self, query: &str, limit: usize) -> Result<Vec<FuzzyMatch>> {
+        let pool = self.pool.clone();
+        let query = query.to_string();
+        blocking(move || pool.fuzzy_search(&query, limit)).await
+    }
+
+    /// 每部电影在首选语言下显示的标题。
+    pub async fn localized_titles(&self, lang: &str) -> Result<HashMap<u32, String>> {
+        let pool = self.pool.clone();
+        let lang = lang.to_string();
+        blocking(move || pool.localized_titles(&lang)).await
+    }
+
+    /// 为电影添加一个别名标题。
+    pub async fn add_alternate_title(
+        &self,
+        movie_id: u32,
+        title: &str,
+        lang: &str,
+        kind: TitleKind,
+    ) -> Result<()> {
+        let pool = self.pool.clone();
+        let title = title.to_string();
+        let lang = lang.to_string();
+        blocking(move || pool.add_alternate_title(movie_id, &title, &lang, kind)).await
+    }
+
+    /// 删除电影中与 `title` 相同的别名标题，返回删除的数量。
+    pub async fn remove_alternate_title(&self, movie_id: u32, title: &str) -> Result<usize> {
+        let pool = self.pool.clone();
+        let title = title.to_string();
+        blocking(move || pool.remove_alternate_title(movie_id, &title)).await
+    }
+
+    /// 列出一部电影的别名标题。
+    pub async fn alternate_titles(&self, movie_id: u32) -> Result<Vec<AlternateTitle>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.alternate_titles(movie_id)).await
+    }
+
+    /// 列出所有电影的别名标题。
+    pub async fn all_alternate_titles(&self) -> Result<Vec<AlternateTitle>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.all_alternate_titles()).await
+    }
+
+    /// ID与 `id` 最接近的至多 `limit` 部电影。
+    pub async fn nearest_movies(&self, id: u32, limit: usize) -> Result<Vec<Movie>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.nearest_movies(id, limit)).await
+    }
+
+    /// 开启或关闭自然键唯一约束。
+    pub async fn set_natural_key_unique(&self, enabled: bool) -> Result<()> {
+        let pool = self.pool.clone();
+        blocking(move || pool.set_natural_key_unique(enabled)).await
+    }
+
+    /// 自然键唯一约束是否开启。
+    pub async fn natural_key_unique(&self) -> Result<bool> {
+        let pool = self.pool.clone();
+        blocking(move || pool.natural_key_unique()).await
+    }
+
+    /// 列出所有导演及其别名。
+    pub async fn list_directors(&self) -> Result<Vec<Director>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.list_directors()).await
+    }
+
+    /// 为导演添加一个别名。
+    pub async fn add_director_alias(&self, name: &str, alias: &str) -> Result<()> {
+        let pool = self.pool.clone();
+        let (name, alias) = (name.to_string(), alias.to_string());
+        blocking(move || pool.add_director_alias(&name, &alias)).await
+    }
+
+    /// 修改导演的规范名称，旧名称保留为别名。
+    pub async fn rename_director(&self, name: &str, new_name: &str) -> Result<()> {
+        let pool = self.pool.clone();
+        let (name, new_name) = (name.to_string(), new_name.to_string());
+        blocking(move || pool.rename_director(&name, &new_name)).await
+    }
+
+    /// 把一位导演合并到另一位，返回转移的电影数量。
+    pub async fn merge_directors(&self, from: &str, into: &str) -> Result<usize> {
+        let pool = self.pool.clone();
+        let (from, into) = (from.to_string(), into.to_string());
+        blocking(move || pool.merge_directors(&from, &into)).await
+    }
+
+    /// 返回搜索使用的 SQL、参数和查询计划。
+    pub async fn explain_search(
+        &self,
+        filter: &MovieFilter,
+    ) -> Result<(String, Vec<String>, Vec<String>)> {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        blocking(move || pool.explain_search(&filter)).await
+    }
+
+    /// 导出整个目录。
+    #[cfg(feature = "toml")]
+    pub async fn dump(&self) -> Result<Dump> {
+        let pool = self.pool.clone();
+        blocking(move || pool.dump()).await
+    }
+
+    /// 用导出的内容替换整个目录。
+    #[cfg(feature = "toml")]
+    pub async fn load(&self, dump: &Dump) -> Result<()> {
+        let pool = self.pool.clone();
+        let dump = dump.clone();
+        blocking(move || pool.load(&dump)).await
+    }
+
+    /// 仍保留的最早一条变更的序号。
+    pub async fn first_change_seq(&self) -> Result<u64> {
+        let pool = self.pool.clone();
+        blocking(move || pool.first_change_seq()).await
+    }
+
+    /// 最新一条变更的序号。
+    pub async fn last_change_seq(&self) -> Result<u64> {
+        let pool = self.pool.clone();
+        blocking(move || pool.last_change_seq()).await
+    }
+
+    /// 读取序号大于 `seq` 的所有变更。
+    pub async fn changes_since(&self, seq: u64) -> Result<Vec<ChangeEvent>> {
+        let pool = self.pool.clone();
+        blocking(move || pool.changes_since(seq)).await
+    }
+
+    /// 持续监听变更，直到 `on_event` 返回 `false`。监听期间占用一个阻塞线程。
+    pub async fn watch_changes(
+        &self,
+        since: u64,
+        interval: Duration,
+        on_event: impl FnMut(ChangeEvent) -> bool + Send + 'static,
+    ) -> Result<()> {
+        let pool = self.pool.clone();
+        blocking(move || pool.watch_changes(since, interval, on_event)).await
+    }
+
+    /// 在写连接上的事务中执行同步闭包：成功时提交，出错时回滚。
+    pub async fn transaction<T, E>(
+        &self,
+        f: impl FnOnce(&MovieManager) -> std::result::Result<T, E> + Send + 'static,
+    ) -> std::result::Result<T, E>
+    where
+        T: Send + 'static,
+        E: From<Error> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        blocking(move || pool.transaction(f)).await
+    }
+
+    /// 在写连接上试运行同步闭包并记录数据变化，然后总是回滚。
+    pub async fn dry_run<T, E>(
+        &self,
+        f: impl FnOnce(&MovieManager) -> std::result::Result<T, E> + Send + 'static,
+    ) -> std::result::Result<(T, ChangeSet), E>
+    where
+        T: Send + 'static,
+        E: From<Error> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        blocking(move || pool.dry_run(f)).await
+    }
+
+    /// 插入一部电影，或更新按ID或自然键匹配到的已有电影。
+    pub async fn upsert_movie(&self, movie: Movie) -> Result<UpsertOutcome> {
+        let pool = self.pool.clone();
+        blocking(move || pool.upsert_movie(movie)).await
+    }
+
+    /// 更新所有满足条件的电影，返回更新的数量。
+    pub async fn update_where(
+        &self,
+        filter: &MovieFilter,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<usize> {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        blocking(move || pool.update_where(&filter, title, director, release_date)).await
+    }
+
+    /// 删除所有满足条件的电影，返回删除的数量。
+    pub async fn delete_where(&self, filter: &MovieFilter) -> Result<usize> {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        blocking(move || pool.delete_where(&filter)).await
+    }
+}
+
+/// 在阻塞线程池上运行闭包。闭包中的 panic 会在调用方重新抛出。
+async fn blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_crud() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = AsyncMovieManager::new(db.to_str().unwrap()).await.unwrap();
+
+        manager
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .await
+            .unwrap();
+        assert!(manager
+            .update_movie(1, Some("Inception 2".into()), None, None)
+            .await
+            .unwrap());
+        assert_eq!(
+            manager.read_movie(1).await.unwrap().unwrap().title,
+            "Inception 2"
+        );
+
+        let filter = MovieFilter::parse(&["nolan"]).unwrap();
+        assert_eq!(manager.search_movies(&filter).await.unwrap().len(), 1);
+        let records = manager
+            .search_fields(&filter, &[Field::Title])
+            .await
+            .unwrap();
+        assert_eq!(records[0].get(Field::Title).unwrap(), "Inception 2");
+        let record = manager.read_fields(1, &[Field::Director]).await.unwrap();
+        assert_eq!(
+            record.unwrap().get(Field::Director).unwrap(),
+            "Christopher Nolan"
+        );
+
+        manager
+            .add_alternate_title(1, "盗梦空间", "zh", TitleKind::Localized)
+            .await
+            .unwrap();
+        assert_eq!(manager.alternate_titles(1).await.unwrap().len(), 1);
+        assert_eq!(manager.all_alternate_titles().await.unwrap().len(), 1);
+        assert_eq!(
+            manager.localized_titles("zh-CN").await.unwrap()[&1],
+            "盗梦空间"
+        );
+        assert_eq!(
+            manager.fuzzy_search("盗梦", 1).await.unwrap()[0].movie.id,
+            1
+        );
+        assert_eq!(
+            manager.remove_alternate_title(1, "盗梦空间").await.unwrap(),
+            1
+        );
+
+        assert!(manager.delete_movie(1).await.unwrap());
+        assert!(manager.list_movies().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_async_manager_api() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = AsyncMovieManager::new(db.to_str().unwrap()).await.unwrap();
+        for (id, title, director) in [
+            (1, "Inception", "Christopher Nolan"),
+            (2, "Tenet", "C. Nolan"),
+        ] {
+            manager
+                .create_movie(id, title.into(), director.into(), "2010/7/16".into())
+                .await
+                .unwrap();
+        }
+        assert_eq!(manager.nearest_movies(9, 1).await.unwrap()[0].id, 2);
+
+        manager.set_natural_key_unique(true).await.unwrap();
+        assert!(manager.natural_key_unique().await.unwrap());
+        assert_eq!(
+            manager
+                .merge_directors("C. Nolan", "Christopher Nolan")
+                .await
+                .unwrap(),
+            1
+        );
+        manager
+            .add_director_alias("Christopher Nolan", "Nolan")
+            .await
+            .unwrap();
+        manager
+            .rename_director("Nolan", "Chris Nolan")
+            .await
+            .unwrap();
+        let directors = manager.list_directors().await.unwrap();
+        assert_eq!(directors[0].name, "Chris Nolan");
+        assert_eq!(directors[0].movie_count, 2);
+
+        let filter = MovieFilter::parse(&["year=2010"]).unwrap();
+        let plan = manager.explain_search(&filter).await.unwrap().2.join("\n");
+        assert!(plan.contains("movies_release_year"));
+
+        let failed = manager
+            .transaction(|m| {
+                m.delete_movie(1)?;
+                Err::<(), _>(Error::Conflict("stop".into()))
+            })
+            .await;
+        assert!(failed.is_err());
+        let ((), changes) = manager
+            .dry_run(|m| m.delete_movie(2).map(|_| ()))
+            .await
+            .unwrap();
+        assert_eq!(changes.deleted.len(), 1);
+        assert_eq!(manager.list_movies().await.unwrap().len(), 2);
+
+        let last = manager.last_change_seq().await.unwrap();
+        assert_eq!(manager.first_change_seq().await.unwrap(), 1);
+        assert_eq!(manager.changes_since(0).await.unwrap().len() as u64, last);
+        manager.delete_movie(2).await.unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        manager
+            .watch_changes(last, Duration::from_millis(10), move |event| {
+                sender.send(event).unwrap();
+                false
+            })
+            .await
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap().movie.title, "Tenet");
+
+        #[cfg(feature = "toml")]
+        {
+            let dump = manager.dump().await.unwrap();
+            manager.delete_movie(1).await.unwrap();
+            manager.load(&dump).await.unwrap();
+            assert_eq!(manager.list_movies().await.unwrap().len(), 1);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! 变更记录与监听。
+//!
+//! movies 表上的触发器把每次插入、更新和删除写入 movie_changes 表，并附带当时的电影内容。
+//! 因为记录在数据库里，其他进程（或其他连接）的修改也能被监听到。
+//!
+//! 数据库不知道有哪些监听者、各自读到了哪里，因此不按读取进度清理，而是只保留最新的
+//! `CHANGE_LOG_LIMIT` 条记录：插入新记录时由触发器删除更早的记录。落后超过这个数量的
+//! 监听者会错过中间的变更，可以用 `first_change_seq` 检查。
+
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::{Movie, MovieManager, Result};
+
+/// 变更的类型。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// 一次变更。删除时 `movie` 是删除前的内容，其余情况是修改后的内容。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// 递增的序号，可以用来从某个位置继续读取。
+    pub seq: u64,
+    pub op: ChangeKind,
+    /// 变更时间（UTC，ISO 8601）。
+    pub changed_at: String,
+    #[serde(flatten)]
+    pub movie: Movie,
+}
+
+/// 变更记录保留的最大条数，更早的记录被触发器删除（见 schema 中的迁移 9）。
+pub const CHANGE_LOG_LIMIT: u64 = 10_000;
+
+impl MovieManager {
+    /// 仍保留的最早一条变更的序号，没有变更时为 0。
+    pub fn first_change_seq(&self) -> Result<u64> {
+        let seq = self.conn.query_row(
+            "SELECT coalesce(min(seq), 0) FROM movie_changes",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(seq)
+    }
+
+    /// 最新一条变更的序号，没有变更时为 0。
+    pub fn last_change_seq(&self) -> Result<u64> {
+        let seq = self.conn.query_row(
+            "SELECT coalesce(max(seq), 0) FROM movie_changes",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(seq)
+    }
+
+    /// 读取序号大于 `seq` 的所有变更，按序号排列。
+    pub fn changes_since(&self, seq: u64) -> Result<Vec<ChangeEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, op, changed_at, movie_id, title, director, release_date
+             FROM movie_changes WHERE seq > ?1 ORDER BY seq",
+        )?;
+        let events = stmt
+            .query_map(params![seq], |row| {
+                let op = match row.get_ref(1)?.as_str()? {
+                    "insert" => ChangeKind::Insert,
+                    "update" => ChangeKind::Update,
+                    _ => ChangeKind::Delete,
+                };
+                Ok(ChangeEvent {
+                    seq: row.get(0)?,
+                    op,
+                    changed_at: row.get(2)?,
+                    movie: Movie::new(row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(events)
+    }
+
+    /// 持续监听序号大于 `since` 的变更，每隔 `interval` 检查一次，直到 `on_event` 返回 `false`。
+    ///
+    /// 通过 `PRAGMA data_version` 判断其他连接是否提交过修改，没有修改时不查询变更表。
+    pub fn watch_changes(
+        &self,
+        since: u64,
+        interval: Duration,
+        mut on_event: impl FnMut(ChangeEvent) -> bool,
+    ) -> Result<()> {
+        let mut seq = since;
+        let mut data_version = None;
+        loop {
+            let current: i64 = self
+                .conn
+                .query_row("PRAGMA data_version", [], |row| row.get(0))?;
+            if data_version != Some(current) {
+                data_version = Some(current);
+                for event in self.changes_since(seq)? {
+                    seq = event.seq;
+                    if !on_event(event) {
+                        return Ok(());
+                    }
+                }
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_other_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let path = db.to_str().unwrap().to_string();
+        let manager = MovieManager::new(&path).unwrap();
+        manager
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        // 内容没有变化的更新不记录
+        manager.update_movie(1, None, None, None).unwrap();
+        manager
+            .rename_director("Christopher Nolan", "C. Nolan")
+            .unwrap();
+        let kinds: Vec<_> = manager
+            .changes_since(0)
+            .unwrap()
+            .iter()
+            .map(|e| e.op)
+            .collect();
+        assert_eq!(kinds, vec![ChangeKind::Insert, ChangeKind::Update]);
+        let since = manager.last_change_seq().unwrap();
+
+        // 另一个连接（相当于另一个进程）写入的修改也能收到
+        let writer = thread::spawn(move || {
+            let other = MovieManager::new(&path).unwrap();
+            other
+                .create_movie(2, "Tenet".into(), "C. Nolan".into(), "2020/8/26".into())
+                .unwrap();
+            other.delete_movie(1).unwrap();
+        });
+        let mut events = Vec::new();
+        manager
+            .watch_changes(since, Duration::from_millis(10), |event| {
+                events.push(event);
+                events.len() < 2
+            })
+            .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(events[0].op, ChangeKind::Insert);
+        assert_eq!(events[0].movie.title, "Tenet");
+        assert_eq!(events[1].op, ChangeKind::Delete);
+        assert_eq!(events[1].movie.director, "C. Nolan");
+
+        // 只保留最新的 CHANGE_LOG_LIMIT 条记录
+        manager
+            .conn
+            .execute(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < ?1)
+                 INSERT INTO movies (id, title, director_id, release_date)
+                 SELECT 100 + i, 'Movie', director_id, '2000/1/1' FROM n, movies WHERE id = 2",
+                params![CHANGE_LOG_LIMIT + 5],
+            )
+            .unwrap();
+        let last = manager.last_change_seq().unwrap();
+        assert_eq!(last, since + 2 + CHANGE_LOG_LIMIT + 5);
+        assert_eq!(
+            manager.first_change_seq().unwrap(),
+            last - CHANGE_LOG_LIMIT + 1
+        );
+        assert_eq!(
+            manager.changes_since(0).unwrap().len() as u64,
+            CHANGE_LOG_LIMIT
+        );
+    }
+}
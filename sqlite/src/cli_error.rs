@@ -0,0 +1,346 @@
+//! 命令行错误与退出码。
+//!
+//! 所有错误在 `main` 中统一按类别映射到固定的退出码，并以文本或 JSON 形式写到标准错误。
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::num::ParseIntError;
+
+use serde_json::{json, Value};
+use sqlite_movies::{ValidationError, XlsxError};
+
+/// 错误类别，每个类别对应一个固定的退出码。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 其他未分类的错误。
+    Other,
+    /// 命令或参数用法错误。
+    Usage,
+    /// 输入内容无效，例如校验失败或无法解析的值。
+    InvalidInput,
+    /// 要操作的电影或导演不存在。
+    NotFound,
+    /// 与已有数据冲突，例如重复的ID。
+    Conflict,
+    /// 数据库或文件读写错误。
+    Storage,
+}
+
+impl ErrorKind {
+    /// 进程退出码。
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::InvalidInput => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Conflict => 5,
+            ErrorKind::Storage => 6,
+        }
+    }
+
+    /// JSON 错误输出中使用的名称。
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Other => "error",
+            ErrorKind::Usage => "usage",
+            ErrorKind::InvalidInput => "invalid_input",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Conflict => "conflict",
+            ErrorKind::Storage => "storage",
+        }
+    }
+}
+
+/// 错误和日志的输出格式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    /// 解析 `--error-format` 或 `--log-format` 的值。
+    pub fn from_str(input: &str) -> Option<OutputFormat> {
+        match input {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// 一条“did you mean”建议。保存原始值，引号等格式只在文本输出中添加。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suggestion {
+    /// 命令、字段等名称，JSON 中是字符串。
+    Name(String),
+    /// 一部电影，JSON 中是 `{"id", "title"}`。
+    Movie { id: u32, title: String },
+}
+
+impl Suggestion {
+    fn to_json(&self) -> Value {
+        match self {
+            Suggestion::Name(name) => json!(name),
+            Suggestion::Movie { id, title } => json!({ "id": id, "title": title }),
+        }
+    }
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Suggestion::Name(name) => write!(f, "'{}'", name),
+            Suggestion::Movie { id, title } => write!(f, "ID {} ({})", id, title),
+        }
+    }
+}
+
+/// 命令行自身产生的错误，带有明确的类别。
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// “did you mean” 建议，文本输出时追加在消息之后。
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CliError {
+            kind,
+            message: message.into(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// 附带建议。
+    pub fn with_suggestions(mut self, suggestions: Vec<Suggestion>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    /// 无法解析的参数：指出参数、期望的类型和一个示例。
+    pub fn invalid_argument(argument: &str, input: &str, expected: &str, example: &str) -> Self {
+        Self::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "invalid {} '{}': expected {}, for example {}",
+                argument, input, expected, example
+            ),
+        )
+    }
+
+    /// 用法错误，消息通常是 `Usage: ...`。
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Usage, message)
+    }
+
+    /// 电影不存在。
+    pub fn movie_not_found(id: u32) -> Self {
+        Self::new(
+            ErrorKind::NotFound,
+            format!("movie with ID {} not found", id),
+        )
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        match self.suggestions.as_slice() {
+            [] => Ok(()),
+            [only] => write!(f, "; did you mean {}?", only),
+            [rest @ .., last] => {
+                let rest: Vec<String> = rest.iter().map(Suggestion::to_string).collect();
+                write!(f, "; did you mean {} or {}?", rest.join(", "), last)
+            }
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// 批处理文件中某一行的错误，保留原始错误以便分类。
+#[derive(Debug)]
+pub struct LineError {
+    pub line: usize,
+    pub source: Box<dyn Error>,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.source)
+    }
+}
+
+impl Error for LineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// 解析电影ID，失败时返回可读的输入错误而不是 Rust 的原始错误信息。
+pub fn parse_id(input: &str) -> Result<u32, CliError> {
+    input.parse().map_err(|_: ParseIntError| {
+        CliError::invalid_argument("<id>", input, "a non-negative integer", "42")
+    })
+}
+
+/// 沿错误链找到第一个可识别的错误，确定类别。
+pub fn classify(error: &(dyn Error + 'static)) -> ErrorKind {
+    let mut current = Some(error);
+    while let Some(e) = current {
+        if let Some(e) = e.downcast_ref::<CliError>() {
+            return e.kind;
+        }
+        if let Some(e) = e.downcast_ref::<sqlite_movies::Error>() {
+            return match e {
+                sqlite_movies::Error::Validation(_) => ErrorKind::InvalidInput,
+                sqlite_movies::Error::NotFound(_) => ErrorKind::NotFound,
+                sqlite_movies::Error::Conflict(_) => ErrorKind::Conflict,
+                sqlite_movies::Error::Sqlite(e) => classify_sqlite(e),
+            };
+        }
+        if let Some(e) = e.downcast_ref::<rusqlite::Error>() {
+            return classify_sqlite(e);
+        }
+        if e.is::<ValidationError>() || e.is::<ParseIntError>() {
+            return ErrorKind::InvalidInput;
+        }
+        if let Some(e) = e.downcast_ref::<csv::Error>() {
+            return match e.kind() {
+                csv::ErrorKind::Io(_) => ErrorKind::Storage,
+                _ => ErrorKind::InvalidInput,
+            };
+        }
+        if let Some(e) = e.downcast_ref::<XlsxError>() {
+            match e {
+                XlsxError::Read(calamine::XlsxError::Io(_)) | XlsxError::Write(_) => {
+                    return ErrorKind::Storage
+                }
+                XlsxError::Read(_) | XlsxError::NoSheet | XlsxError::MissingColumn(_) => {
+                    return ErrorKind::InvalidInput
+                }
+                XlsxError::Store(_) => {}
+            }
+        }
+        if e.is::<io::Error>() {
+            return ErrorKind::Storage;
+        }
+        current = e.source();
+    }
+    ErrorKind::Other
+}
+
+/// 唯一约束等约束冲突视为数据冲突，其余 SQLite 错误视为存储错误。
+fn classify_sqlite(error: &rusqlite::Error) -> ErrorKind {
+    match error {
+        rusqlite::Error::SqliteFailure(e, _)
+            if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            ErrorKind::Conflict
+        }
+        _ => ErrorKind::Storage,
+    }
+}
+
+/// 把错误转换为 JSON：`{"error": {"kind", "code", "message", ...}}`。
+///
+/// 校验错误附带 `fields` 数组，批处理错误附带出错的 `line`，有建议时附带 `suggestions` 数组。
+pub fn to_json(error: &(dyn Error + 'static)) -> Value {
+    let kind = classify(error);
+    let mut body = json!({
+        "kind": kind.name(),
+        "code": kind.exit_code(),
+        "message": error.to_string(),
+    });
+
+    let mut current = Some(error);
+    while let Some(e) = current {
+        if let Some(e) = e.downcast_ref::<LineError>() {
+            body["line"] = json!(e.line);
+        }
+        if let Some(e) = e.downcast_ref::<CliError>() {
+            if !e.suggestions.is_empty() {
+                let suggestions: Vec<Value> =
+                    e.suggestions.iter().map(Suggestion::to_json).collect();
+                body["suggestions"] = json!(suggestions);
+            }
+        }
+        if let Some(e) = e.downcast_ref::<ValidationError>() {
+            let fields: Vec<_> = e
+                .errors
+                .iter()
+                .map(|f| json!({ "field": f.field, "message": f.message }))
+                .collect();
+            body["fields"] = json!(fields);
+        }
+        current = e.source();
+    }
+    json!({ "error": body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let not_found: Box<dyn Error> = CliError::movie_not_found(7).into();
+        assert_eq!(classify(not_found.as_ref()).exit_code(), 4);
+
+        // 批处理中的错误按原始错误分类
+        let validation = sqlite_movies::Error::Validation(ValidationError {
+            errors: vec![sqlite_movies::FieldError {
+                field: "title",
+                message: "must not be empty".to_string(),
+            }],
+        });
+        let line = LineError {
+            line: 3,
+            source: Box::new(validation),
+        };
+        assert_eq!(classify(&line), ErrorKind::InvalidInput);
+        let value = to_json(&line);
+        assert_eq!(value["error"]["kind"], "invalid_input");
+        assert_eq!(value["error"]["line"], 3);
+        assert_eq!(value["error"]["fields"][0]["field"], "title");
+
+        let invalid = parse_id("abc").unwrap_err();
+        assert_eq!(invalid.kind, ErrorKind::InvalidInput);
+        assert_eq!(
+            invalid.to_string(),
+            "invalid <id> 'abc': expected a non-negative integer, for example 42"
+        );
+        let unknown = CliError::usage("unknown command 'lsit'")
+            .with_suggestions(vec![Suggestion::Name("list".to_string())]);
+        assert_eq!(
+            unknown.to_string(),
+            "unknown command 'lsit'; did you mean 'list'?"
+        );
+        // JSON 中是原始值，不带引号
+        assert_eq!(to_json(&unknown)["error"]["suggestions"][0], "list");
+        let missing = CliError::movie_not_found(7).with_suggestions(vec![
+            Suggestion::Movie {
+                id: 1,
+                title: "Inception".to_string(),
+            },
+            Suggestion::Movie {
+                id: 9,
+                title: "Tenet".to_string(),
+            },
+        ]);
+        assert_eq!(
+            missing.to_string(),
+            "movie with ID 7 not found; did you mean ID 1 (Inception) or ID 9 (Tenet)?"
+        );
+        let value = to_json(&missing);
+        assert_eq!(value["error"]["suggestions"][1]["id"], 9);
+        assert_eq!(value["error"]["suggestions"][1]["title"], "Tenet");
+        let io = io::Error::new(io::ErrorKind::NotFound, "missing");
+        assert_eq!(classify(&io), ErrorKind::Storage);
+    }
+}
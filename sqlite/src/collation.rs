@@ -0,0 +1,90 @@
+//! Unicode 排序规则与文本归一化。
+//!
+//! SQLite 默认的 BINARY 排序按字节比较，`LIKE` 也只对 ASCII 忽略大小写，
+//! 因此中文、日文和带重音的标题排序和匹配都不理想。`MovieManager` 打开的每个连接上都会注册：
+//! - `UNICODE` 排序规则：先比较折叠后的文本，相同时再按 NFC 文本比较，保证结果稳定；
+//! - `fold(text)` 函数：NFD 分解后去掉组合附加符号再转小写，用于不区分大小写和重音的匹配。
+//!
+//! 表结构中不使用它们，数据库文件仍然可以用普通的 sqlite3 打开。
+
+use std::cmp::Ordering;
+
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::Result;
+
+/// 排序规则的名称，用法：`ORDER BY title COLLATE UNICODE`。
+pub const COLLATION: &str = "UNICODE";
+
+/// 转换为 NFC 形式。写入数据库的文本都先经过它。
+pub fn nfc(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// 折叠大小写和重音：`Amélie` 和 `AMELIE` 折叠后相同。
+pub fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|&c| !is_combining_mark(c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// `UNICODE` 排序规则的比较函数。
+pub fn unicode_cmp(a: &str, b: &str) -> Ordering {
+    fold(a).cmp(&fold(b)).then_with(|| a.nfc().cmp(b.nfc()))
+}
+
+/// 在连接上注册排序规则和 `fold` 函数。
+pub(crate) fn register(conn: &Connection) -> Result<()> {
+    conn.create_collation(COLLATION, unicode_cmp)?;
+    conn.create_scalar_function(
+        "fold",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|text| fold(&text))),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_and_collation() {
+        assert_eq!(fold("Amélie"), "amelie");
+        assert_eq!(fold("ÉCOLE"), fold("école"));
+        assert_eq!(fold("千と千尋の神隠し"), "千と千尋の神隠し");
+        // 组合形式与预组合形式归一化后相同
+        assert_eq!(nfc("e\u{301}"), "\u{e9}");
+
+        let conn = Connection::open_in_memory().unwrap();
+        register(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (name TEXT);
+             INSERT INTO t VALUES ('Zodiac'), ('Élite'), ('amélie'), ('Batman'), ('阿凡达');",
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM t ORDER BY name COLLATE UNICODE")
+            .unwrap();
+        let names: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(names, ["amélie", "Batman", "Élite", "Zodiac", "阿凡达"]);
+
+        let found: String = conn
+            .query_row(
+                "SELECT name FROM t WHERE instr(fold(name), fold('AMELIE')) > 0",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(found, "amélie");
+    }
+}
@@ -0,0 +1,346 @@
+//! 导演管理。
+//!
+//! 每位导演在 directors 表中有一个规范名称，并可以有多个别名。创建或更新电影时，
+//! 导演名称会先按别名、再按规范名称解析到同一位导演；都不存在时新建导演。
+//!
+//! 名称先精确匹配（只忽略 ASCII 大小写），再按 `collation::fold` 折叠后的
+//! `name_key` / `alias_key` 匹配，因此 `émile zola` 和 `Émile Zola` 是同一位导演。
+//! 精确匹配优先，已有的仅大小写或重音不同的重复导演仍然可以分别指定并合并。
+
+use rusqlite::{params, OptionalExtension};
+use tracing::instrument;
+
+use crate::collation::{fold, nfc, COLLATION};
+use crate::{Error, FieldError, MovieManager, Result, ValidationError};
+
+/// 一位导演及其别名。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Director {
+    pub id: u32,
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// 引用该导演的电影数量。
+    pub movie_count: u32,
+}
+
+impl MovieManager {
+    /// 按别名或规范名称查找导演的ID。
+    pub(crate) fn find_director_id(&self, name: &str) -> Result<Option<u32>> {
+        let id = self
+            .conn
+            .query_row(
+                "SELECT director_id FROM director_aliases WHERE alias = trim(?1)
+                 UNION ALL
+                 SELECT id FROM directors WHERE name = trim(?1)
+                 UNION ALL
+                 SELECT director_id FROM director_aliases WHERE alias_key = fold(trim(?1))
+                 UNION ALL
+                 SELECT id FROM directors WHERE name_key = fold(trim(?1))
+                 LIMIT 1",
+                params![nfc(name)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    /// 解析导演名称，不存在时创建新导演。
+    pub(crate) fn director_id(&self, name: &str) -> Result<u32> {
+        if let Some(id) = self.find_director_id(name)? {
+            return Ok(id);
+        }
+        self.conn.execute(
+            "INSERT INTO directors (name, name_key, name_roman, name_initials)
+             VALUES (trim(?1), fold(trim(?1)), romanize(?1), romanize_initials(?1))",
+            params![nfc(name)],
+        )?;
+        Ok(self.conn.last_insert_rowid() as u32)
+    }
+
+    /// 按名称或别名查找导演，不存在时返回 `Error::NotFound`。
+    fn require_director(&self, name: &str) -> Result<u32> {
+        self.find_director_id(name)?
+            .ok_or_else(|| Error::NotFound(format!("director '{}' not found", name.trim())))
+    }
+
+    /// 列出所有导演，按名称排序（`UNICODE` 排序规则）。
+    pub fn list_directors(&self) -> Result<Vec<Director>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT d.id, d.name, (SELECT COUNT(*) FROM movies m WHERE m.director_id = d.id)
+             FROM directors d ORDER BY d.name COLLATE {}",
+            COLLATION
+        ))?;
+        let mut directors = stmt
+            .query_map([], |row| {
+                Ok(Director {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    aliases: Vec::new(),
+                    movie_count: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT alias FROM director_aliases WHERE director_id = ?1 ORDER BY alias")?;
+        for director in &mut directors {
+            director.aliases = stmt
+                .query_map(params![director.id], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+        }
+        Ok(directors)
+    }
+
+    /// 为导演添加一个别名。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn add_director_alias(&self, name: &str, alias: &str) -> Result<()> {
+        let id = self.require_director(name)?;
+        check_name(alias, self.rules.max_director_len)?;
+        match self.find_director_id(alias)? {
+            Some(existing) if existing == id => Ok(()),
+            Some(_) => Err(Error::Conflict(format!(
+                "'{}' already refers to another director",
+                alias.trim()
+            ))),
+            None => {
+                self.conn.execute(
+                    "INSERT INTO director_aliases (alias, alias_key, director_id)
+                     VALUES (trim(?1), fold(trim(?1)), ?2)",
+                    params![nfc(alias), id],
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// 按给定的ID恢复一位导演及其别名，供 `load` 使用。
+    ///
+    /// 不经过名称解析，已有的仅大小写或重音不同的重复导演保持分开。
+    #[cfg(feature = "toml")]
+    pub(crate) fn restore_director(&self, id: u32, name: &str, aliases: &[String]) -> Result<()> {
+        check_name(name, self.rules.max_director_len)?;
+        self.conn.execute(
+            "INSERT INTO directors (id, name, name_key, name_roman, name_initials)
+             VALUES (?1, trim(?2), fold(trim(?2)), romanize(?2), romanize_initials(?2))",
+            params![id, nfc(name)],
+        )?;
+        for alias in aliases {
+            check_name(alias, self.rules.max_director_len)?;
+            self.conn.execute(
+                "INSERT INTO director_aliases (alias, alias_key, director_id)
+                 VALUES (trim(?1), fold(trim(?1)), ?2)",
+                params![nfc(alias), id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 修改导演的规范名称，旧名称保留为别名。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn rename_director(&self, name: &str, new_name: &str) -> Result<()> {
+        let new_name = &nfc(new_name);
+        let id = self.require_director(name)?;
+        check_name(new_name, self.rules.max_director_len)?;
+        if let Some(other) = self.find_director_id(new_name)? {
+            if other != id {
+                return Err(Error::Conflict(format!(
+                    "director '{}' already exists; use merge instead",
+                    new_name.trim()
+                )));
+            }
+        }
+
+        self.transaction(|manager| {
+            let old_name: String = manager.conn.query_row(
+                "SELECT name FROM directors WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )?;
+            manager.conn.execute(
+                "DELETE FROM director_aliases WHERE alias_key = fold(trim(?1))",
+                params![new_name],
+            )?;
+            manager.conn.execute(
+                "UPDATE directors SET name = trim(?1), name_key = fold(trim(?1)),
+                     name_roman = romanize(?1), name_initials = romanize_initials(?1)
+                 WHERE id = ?2",
+                params![new_name, id],
+            )?;
+            if fold(&old_name) != fold(new_name.trim()) {
+                manager.conn.execute(
+                    "INSERT OR IGNORE INTO director_aliases (alias, alias_key, director_id)
+                     VALUES (?1, fold(?1), ?2)",
+                    params![old_name, id],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// 把一位导演合并到另一位：电影和别名都转移过去，原名称变为别名。返回转移的电影数量。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn merge_directors(&self, from: &str, into: &str) -> Result<usize> {
+        let from_id = self.require_director(from)?;
+        let into_id = self.require_director(into)?;
+        if from_id == into_id {
+            return Err(Error::Conflict(format!(
+                "'{}' and '{}' are already the same director",
+                from.trim(),
+                into.trim()
+            )));
+        }
+
+        self.transaction(|manager| {
+            let moved = manager.conn.execute(
+                "UPDATE movies SET director_id = ?1 WHERE director_id = ?2",
+                params![into_id, from_id],
+            )?;
+            manager.conn.execute(
+                "UPDATE director_aliases SET director_id = ?1 WHERE director_id = ?2",
+                params![into_id, from_id],
+            )?;
+            manager.conn.execute(
+                "INSERT OR IGNORE INTO director_aliases (alias, alias_key, director_id)
+                 SELECT name, name_key, ?1 FROM directors WHERE id = ?2",
+                params![into_id, from_id],
+            )?;
+            manager
+                .conn
+                .execute("DELETE FROM directors WHERE id = ?1", params![from_id])?;
+            Ok(moved)
+        })
+    }
+}
+
+/// 检查导演名称非空且不超过最大长度。
+fn check_name(name: &str, max_len: usize) -> Result<()> {
+    let len = name.trim().chars().count();
+    let message = if len == 0 {
+        "must not be empty".to_string()
+    } else if len > max_len {
+        format!("must be at most {} characters (got {})", max_len, len)
+    } else {
+        return Ok(());
+    };
+    Err(Error::Validation(ValidationError {
+        errors: vec![FieldError {
+            field: "director",
+            message,
+        }],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MovieFilter;
+
+    #[test]
+    fn test_alias_rename_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = MovieManager::new(db.to_str().unwrap()).unwrap();
+        manager
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        manager
+            .create_movie(
+                2,
+                "Interstellar".into(),
+                "C. Nolan".into(),
+                "2014/11/5".into(),
+            )
+            .unwrap();
+        assert_eq!(manager.list_directors().unwrap().len(), 2);
+
+        // 合并后两部电影属于同一位导演，旧的拼写作为别名继续可用
+        assert_eq!(
+            manager
+                .merge_directors("c. nolan", "Christopher Nolan")
+                .unwrap(),
+            1
+        );
+        let directors = manager.list_directors().unwrap();
+        assert_eq!(directors.len(), 1);
+        assert_eq!(directors[0].aliases, vec!["C. Nolan"]);
+        assert_eq!(directors[0].movie_count, 2);
+        // director= 也匹配导演的别名
+        let filter = MovieFilter::parse(&["director=C. Nolan"]).unwrap();
+        assert_eq!(manager.search_movies(&filter).unwrap().len(), 2);
+        manager
+            .create_movie(3, "Tenet".into(), "C. NOLAN".into(), "2020/8/26".into())
+            .unwrap();
+        assert_eq!(
+            manager.read_movie(3).unwrap().unwrap().director,
+            "Christopher Nolan"
+        );
+
+        manager
+            .add_director_alias("Christopher Nolan", "Nolan")
+            .unwrap();
+        manager
+            .rename_director("nolan", "Christopher Edward Nolan")
+            .unwrap();
+        assert_eq!(
+            manager.read_movie(1).unwrap().unwrap().director,
+            "Christopher Edward Nolan"
+        );
+        assert!(matches!(
+            manager.rename_director("Nobody", "Someone"),
+            Err(Error::NotFound(_))
+        ));
+        manager
+            .create_movie(
+                4,
+                "Dune".into(),
+                "Denis Villeneuve".into(),
+                "2021/10/22".into(),
+            )
+            .unwrap();
+        assert!(matches!(
+            manager.rename_director("Denis Villeneuve", "Christopher Nolan"),
+            Err(Error::Conflict(_))
+        ));
+
+        // 只有非 ASCII 字母的大小写或重音不同，仍然解析为同一位导演
+        manager
+            .create_movie(5, "Nana".into(), "Émile Zola".into(), "1926/6/25".into())
+            .unwrap();
+        manager
+            .create_movie(
+                6,
+                "Germinal".into(),
+                "émile zola".into(),
+                "1993/9/29".into(),
+            )
+            .unwrap();
+        assert_eq!(
+            manager.read_movie(6).unwrap().unwrap().director,
+            "Émile Zola"
+        );
+        manager.add_director_alias("emile zola", "Zola").unwrap();
+        assert!(matches!(
+            manager.add_director_alias("Denis Villeneuve", "ZOLA"),
+            Err(Error::Conflict(_))
+        ));
+        // 升级前留下的重复导演可以按精确名称分别指定并合并
+        manager
+            .conn
+            .execute(
+                "INSERT INTO directors (name, name_key) VALUES ('ÉMILE ZOLÀ', 'emile zola')",
+                [],
+            )
+            .unwrap();
+        assert_eq!(
+            manager.merge_directors("ÉMILE ZOLÀ", "Émile Zola").unwrap(),
+            0
+        );
+        assert_eq!(manager.list_directors().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,228 @@
+//! 数据库健康检查与修复。
+//!
+//! `check` 以只读方式打开数据库，不执行迁移，因此能发现过旧或过新的结构版本；
+//! `repair` 修复能自动修复的问题，无法自动修复的（例如无效日期）只报告。
+
+use std::fs;
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::schema::{schema_version, SCHEMA_VERSION};
+use crate::{Movie, MovieManager, Result, ValidationError, ValidationRules};
+
+/// 一条外键约束违规。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    /// 被引用但不存在的表。
+    pub parent: String,
+}
+
+/// 健康检查的结果。
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// 数据库文件大小（字节）。
+    pub file_size: u64,
+    pub page_size: u64,
+    pub page_count: u64,
+    /// 空闲页数量，可以通过 VACUUM 回收。
+    pub free_pages: u64,
+    /// 数据库中记录的结构版本。
+    pub schema_version: u32,
+    /// `PRAGMA integrity_check` 报告的问题，没有问题时为空。
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    /// 字段无效的电影。结构版本不是当前版本时不检查。
+    pub invalid_movies: Vec<(Movie, ValidationError)>,
+}
+
+impl HealthReport {
+    /// 结构版本是否与当前代码一致。
+    pub fn schema_current(&self) -> bool {
+        self.schema_version == SCHEMA_VERSION
+    }
+
+    /// 发现的问题数量。
+    pub fn problem_count(&self) -> usize {
+        self.storage_problem_count() + self.invalid_movies.len()
+    }
+
+    /// 数据库本身的问题（结构版本、完整性、外键）数量，不含字段无效的电影。
+    pub fn storage_problem_count(&self) -> usize {
+        usize::from(!self.schema_current())
+            + self.integrity_errors.len()
+            + self.foreign_key_violations.len()
+    }
+}
+
+/// `repair` 完成的修复。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    /// 迁移前的结构版本，没有执行迁移时为 `None`。
+    pub migrated_from: Option<u32>,
+    /// 去掉了首尾空白的电影和导演行数。
+    pub trimmed: usize,
+    /// 删除的、指向不存在导演的别名数量。
+    pub orphan_aliases: usize,
+}
+
+/// 检查数据库文件，不做任何修改。文件不存在时返回错误而不是创建新数据库。
+pub fn check(path: &str) -> Result<HealthReport> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let pragma = |name: &str| -> Result<u64> {
+        Ok(conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))?)
+    };
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let integrity_errors = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter(|message| !matches!(message.as_deref(), Ok("ok")))
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let foreign_key_violations = stmt
+        .query_map([], |row| {
+            Ok(ForeignKeyViolation {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let schema_version = schema_version(&conn)?;
+    let invalid_movies = if schema_version == SCHEMA_VERSION {
+        invalid_movies(&conn)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(HealthReport {
+        file_size: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        page_size: pragma("page_size")?,
+        page_count: pragma("page_count")?,
+        free_pages: pragma("freelist_count")?,
+        schema_version,
+        integrity_errors,
+        foreign_key_violations,
+        invalid_movies,
+    })
+}
+
+/// 找出未通过校验的电影。缺少导演的电影以空导演参与校验。
+fn invalid_movies(conn: &Connection) -> Result<Vec<(Movie, ValidationError)>> {
+    let rules = ValidationRules::default();
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.title, coalesce(d.name, ''), m.release_date
+         FROM movies m LEFT JOIN directors d ON d.id = m.director_id
+         ORDER BY m.id",
+    )?;
+    let movies = stmt
+        .query_map([], |row| {
+            Ok(Movie::new(
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(movies
+        .into_iter()
+        .filter_map(|movie| match movie.validate(&rules) {
+            Ok(()) => None,
+            Err(e) => Some((movie, e)),
+        })
+        .collect())
+}
+
+/// 修复能自动修复的问题：执行迁移、去掉字段首尾空白、删除孤立的别名，然后重建索引并回收空闲页。
+pub fn repair(path: &str) -> Result<RepairReport> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    let before = schema_version(&conn)?;
+    let manager = MovieManager::from_connection(conn)?;
+    let mut report = RepairReport {
+        migrated_from: (before < SCHEMA_VERSION).then_some(before),
+        ..RepairReport::default()
+    };
+
+    manager.transaction(|m| -> Result<()> {
+        report.trimmed = m.conn.execute(
+            "UPDATE movies SET title = trim(title), release_date = trim(release_date)
+             WHERE title != trim(title) OR release_date != trim(release_date)",
+            [],
+        )?;
+        // 去掉空白后与已有导演重名的保持不变
+        report.trimmed += m.conn.execute(
+            "UPDATE OR IGNORE directors SET name = trim(name) WHERE name != trim(name)",
+            [],
+        )?;
+        report.orphan_aliases = m.conn.execute(
+            "DELETE FROM director_aliases WHERE director_id NOT IN (SELECT id FROM directors)",
+            [],
+        )?;
+        Ok(())
+    })?;
+    // VACUUM 不能在事务中执行
+    manager.conn.execute_batch("REINDEX; VACUUM;")?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_and_repair() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let path = db.to_str().unwrap();
+        assert!(check(path).is_err());
+
+        let manager = MovieManager::new(path).unwrap();
+        manager
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        manager
+            .add_director_alias("Christopher Nolan", "Nolan")
+            .unwrap();
+        // 绕过校验和外键约束写入有问题的数据
+        manager
+            .conn
+            .execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                 INSERT INTO movies (id, title, director_id, release_date) VALUES (2, ' Tenet ', 1, 'banana');
+                 INSERT INTO director_aliases (alias, director_id) VALUES ('Ghost', 99);",
+            )
+            .unwrap();
+        drop(manager);
+
+        let report = check(path).unwrap();
+        assert!(report.schema_current());
+        assert!(report.integrity_errors.is_empty());
+        assert_eq!(report.foreign_key_violations.len(), 1);
+        assert_eq!(report.foreign_key_violations[0].table, "director_aliases");
+        assert_eq!(report.invalid_movies.len(), 1);
+        assert_eq!(report.invalid_movies[0].0.id, 2);
+        assert_eq!(report.problem_count(), 2);
+        assert_eq!(report.storage_problem_count(), 1);
+
+        let repaired = repair(path).unwrap();
+        assert_eq!(repaired.migrated_from, None);
+        assert_eq!(repaired.trimmed, 1);
+        assert_eq!(repaired.orphan_aliases, 1);
+
+        // 无效日期无法自动修复
+        let report = check(path).unwrap();
+        assert_eq!(report.problem_count(), 1);
+        assert_eq!(report.storage_problem_count(), 0);
+        let manager = MovieManager::new(path).unwrap();
+        assert_eq!(manager.read_movie(2).unwrap().unwrap().title, "Tenet");
+    }
+}
@@ -0,0 +1,232 @@
+//! 以 TOML 文本导出和导入整个目录。
+//!
+//! 导出的内容是确定的：导演按名称排序，别名按字母排序，电影按ID排序，别名标题按电影ID排序，每条记录一个块。
+//! 因此可以把导出文件放进版本库，目录的修改在 diff 中清晰可读。
+//!
+//! 导演连同ID一起导出，装载时按ID恢复，电影按导出文件中导演的名称找到对应的ID，
+//! 不经过折叠后的名称解析，因此仅大小写或重音不同的重复导演不会被合并。
+//! 只接受与当前结构版本相同的导出文件，其他版本的导出请用对应版本的程序重新导出。
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::SCHEMA_VERSION;
+use crate::{AlternateTitle, Error, Movie, MovieManager, Result};
+
+/// 导出文件开头的说明。
+const HEADER: &str = "# Movie catalog dump. Rebuild a database from it with `load <file>`.\n\n";
+
+/// 整个目录的快照。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dump {
+    /// 导出时的结构版本。
+    pub schema_version: u32,
+    /// 是否开启了自然键唯一约束。
+    #[serde(default)]
+    pub natural_key_unique: bool,
+    #[serde(default)]
+    pub directors: Vec<DumpDirector>,
+    #[serde(default)]
+    pub movies: Vec<Movie>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternate_titles: Vec<AlternateTitle>,
+}
+
+/// 导出的一位导演。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpDirector {
+    pub id: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl Dump {
+    /// 转换为 TOML 文本。
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string(self).expect("a dump always serializes to TOML");
+        format!("{}{}", HEADER, body)
+    }
+
+    /// 解析 TOML 文本。
+    pub fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+impl MovieManager {
+    /// 导出整个目录。
+    pub fn dump(&self) -> Result<Dump> {
+        let mut directors: Vec<DumpDirector> = self
+            .list_directors()?
+            .into_iter()
+            .map(|d| DumpDirector {
+                id: d.id,
+                name: d.name,
+                aliases: d.aliases,
+            })
+            .collect();
+        // list_directors 按 UNICODE 排序规则排序，这里再按字节排序以保证结果与排序规则无关
+        directors.sort_by(|a, b| a.name.cmp(&b.name));
+        for director in &mut directors {
+            director.aliases.sort();
+        }
+
+        Ok(Dump {
+            schema_version: SCHEMA_VERSION,
+            natural_key_unique: self.natural_key_unique()?,
+            directors,
+            movies: self.list_movies()?,
+            alternate_titles: self.all_alternate_titles()?,
+        })
+    }
+
+    /// 用快照替换目录中的全部导演、电影和别名标题，在一个事务中完成。
+    ///
+    /// 每部电影都要通过校验；任何一条失败都会回滚，数据库保持原样。
+    pub fn load(&self, dump: &Dump) -> Result<()> {
+        if dump.schema_version != SCHEMA_VERSION {
+            return Err(Error::Conflict(format!(
+                "the dump has schema version {}, but this build expects {}; dump it again with a matching build",
+                dump.schema_version, SCHEMA_VERSION
+            )));
+        }
+
+        self.transaction(|manager| {
+            // 先关闭唯一约束，装载完成后按快照的设置重新开启
+            manager.set_natural_key_unique(false)?;
+            manager.conn.execute_batch(
+                "DELETE FROM alternate_titles;
+                 DELETE FROM movies;
+                 DELETE FROM director_aliases;
+                 DELETE FROM directors;",
+            )?;
+            for director in &dump.directors {
+                manager.restore_director(director.id, &director.name, &director.aliases)?;
+            }
+            // 电影的导演名称来自导出的导演，按原样对应到导出的ID
+            let director_ids: HashMap<&str, u32> = dump
+                .directors
+                .iter()
+                .map(|d| (d.name.as_str(), d.id))
+                .collect();
+            for movie in &dump.movies {
+                let director_id = director_ids.get(movie.director.as_str()).copied();
+                let movie = movie.clone().normalized();
+                movie.validate(&manager.rules)?;
+                let director_id = match director_id {
+                    Some(id) => id,
+                    // 手写的导出文件可以省略导演，这时按名称解析或新建
+                    None => manager.director_id(&movie.director)?,
+                };
+                manager.insert_movie(&movie, director_id)?;
+            }
+            for alternate in &dump.alternate_titles {
+                manager.add_alternate_title(
+                    alternate.movie_id,
+                    &alternate.title,
+                    &alternate.lang,
+                    alternate.kind,
+                )?;
+            }
+            manager.set_natural_key_unique(dump.natural_key_unique)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = MovieManager::new(dir.path().join("a.db").to_str().unwrap()).unwrap();
+        source
+            .create_movie(
+                2,
+                "Tenet".into(),
+                "Christopher Nolan".into(),
+                "2020/8/26".into(),
+            )
+            .unwrap();
+        source
+            .create_movie(
+                1,
+                "Dune".into(),
+                "Denis Villeneuve".into(),
+                "2021/10/22".into(),
+            )
+            .unwrap();
+        source
+            .add_director_alias("Christopher Nolan", "Nolan")
+            .unwrap();
+        source
+            .add_director_alias("Christopher Nolan", "C. Nolan")
+            .unwrap();
+        source.set_natural_key_unique(true).unwrap();
+        source
+            .add_alternate_title(1, "Dune: Part One", "en", crate::TitleKind::Working)
+            .unwrap();
+
+        let text = source.dump().unwrap().to_toml();
+        assert!(text.contains("aliases = [\"C. Nolan\", \"Nolan\"]"));
+        assert!(text.find("id = 1").unwrap() < text.find("id = 2").unwrap());
+        assert!(text.contains("kind = \"working\""));
+
+        // 装载到已有数据的数据库会替换原有内容
+        let target = MovieManager::new(dir.path().join("b.db").to_str().unwrap()).unwrap();
+        target
+            .create_movie(9, "Old".into(), "Someone".into(), "2000/1/1".into())
+            .unwrap();
+        target.load(&Dump::from_toml(&text).unwrap()).unwrap();
+        assert_eq!(target.dump().unwrap().to_toml(), text);
+        assert!(target.read_movie(9).unwrap().is_none());
+
+        // 无效的电影使整个装载回滚
+        let mut bad = Dump::from_toml(&text).unwrap();
+        bad.movies[0].release_date = "banana".into();
+        assert!(matches!(target.load(&bad), Err(Error::Validation(_))));
+        assert_eq!(target.list_movies().unwrap().len(), 2);
+
+        // 其他结构版本的导出文件被拒绝
+        let mut old = Dump::from_toml(&text).unwrap();
+        old.schema_version -= 1;
+        assert!(matches!(target.load(&old), Err(Error::Conflict(_))));
+    }
+
+    #[test]
+    fn test_load_keeps_duplicate_directors() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = MovieManager::new(dir.path().join("a.db").to_str().unwrap()).unwrap();
+        source
+            .create_movie(1, "Nana".into(), "Émile Zola".into(), "1926/6/25".into())
+            .unwrap();
+        // 迁移之前留下的重复导演，名称只有重音不同
+        source
+            .conn
+            .execute(
+                "INSERT INTO directors (id, name, name_key) VALUES (7, 'Emile Zola', 'emile zola')",
+                [],
+            )
+            .unwrap();
+        source
+            .conn
+            .execute(
+                "INSERT INTO movies (id, title, director_id, release_date) VALUES (2, 'Therese', 7, '1928/1/1')",
+                [],
+            )
+            .unwrap();
+
+        let dump = source.dump().unwrap();
+        let target = MovieManager::new(dir.path().join("b.db").to_str().unwrap()).unwrap();
+        target.load(&dump).unwrap();
+        assert_eq!(target.list_directors().unwrap().len(), 2);
+        assert_eq!(
+            target.read_movie(2).unwrap().unwrap().director,
+            "Emile Zola"
+        );
+        assert_eq!(target.dump().unwrap(), dump);
+    }
+}
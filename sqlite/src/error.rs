@@ -0,0 +1,53 @@
+//! 库的统一错误类型。
+
+use std::fmt;
+
+use crate::validation::ValidationError;
+
+/// 电影管理器返回的错误。
+#[derive(Debug)]
+pub enum Error {
+    /// 电影字段未通过校验。
+    Validation(ValidationError),
+    /// 要操作的对象不存在。
+    NotFound(String),
+    /// 操作与已有数据冲突。
+    Conflict(String),
+    /// 底层 SQLite 错误。
+    Sqlite(rusqlite::Error),
+}
+
+/// 使用 [`Error`] 的结果类型。
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Validation(e) => write!(f, "{}", e),
+            Error::NotFound(message) | Error::Conflict(message) => write!(f, "{}", message),
+            Error::Sqlite(e) => write!(f, "storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Validation(e) => Some(e),
+            Error::NotFound(_) | Error::Conflict(_) => None,
+            Error::Sqlite(e) => Some(e),
+        }
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        Error::Validation(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Sqlite(e)
+    }
+}
@@ -0,0 +1,197 @@
+//! 字段选择。
+//!
+//! 只需要部分字段时（例如只要ID和标题），用 `Field` 列表指定，
+//! `MovieStore::read_fields` 和 `search_fields` 返回只包含这些字段的 `MovieRecord`，
+//! SQLite 后端只查询对应的列。
+
+use std::fmt;
+
+use rusqlite::Row;
+use serde::Serialize;
+
+use crate::fuzzy::suggest;
+use crate::Movie;
+
+/// 电影的一个字段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Title,
+    Director,
+    ReleaseDate,
+}
+
+impl Field {
+    /// 全部字段，按默认的输出顺序排列。
+    pub const ALL: [Field; 4] = [Field::Id, Field::Title, Field::Director, Field::ReleaseDate];
+
+    /// 字段名，同时也是 `movie_details` 视图中的列名。
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Title => "title",
+            Field::Director => "director",
+            Field::ReleaseDate => "release_date",
+        }
+    }
+
+    /// 文本输出中使用的标签。
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::Id => "ID",
+            Field::Title => "Title",
+            Field::Director => "Director",
+            Field::ReleaseDate => "Release Date",
+        }
+    }
+
+    /// 解析逗号分隔的字段列表，例如 `id,title`。重复的字段只保留第一个。
+    pub fn parse_list(input: &str) -> Result<Vec<Field>, String> {
+        let mut fields = Vec::new();
+        for name in input.split(',').map(str::trim) {
+            let field = Field::ALL
+                .into_iter()
+                .find(|f| f.name() == name)
+                .ok_or_else(|| {
+                    let names = Field::ALL.map(Field::name);
+                    match suggest(name, &names) {
+                        Some(closest) => {
+                            format!("unknown field '{}'; did you mean '{}'?", name, closest)
+                        }
+                        None => format!(
+                            "unknown field '{}' (expected id, title, director or release_date)",
+                            name
+                        ),
+                    }
+                })?;
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        Ok(fields)
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 只包含选中字段的电影，未选中的字段为 `None`。
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MovieRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub director: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+}
+
+impl MovieRecord {
+    /// 从完整的电影中取出选中的字段。
+    pub fn from_movie(movie: &Movie, fields: &[Field]) -> Self {
+        let mut record = MovieRecord::default();
+        for field in fields {
+            match field {
+                Field::Id => record.id = Some(movie.id),
+                Field::Title => record.title = Some(movie.title.clone()),
+                Field::Director => record.director = Some(movie.director.clone()),
+                Field::ReleaseDate => record.release_date = Some(movie.release_date.clone()),
+            }
+        }
+        record
+    }
+
+    /// 从查询结果中读取一行，列的顺序与 `fields` 相同。
+    pub(crate) fn from_row(row: &Row, fields: &[Field]) -> rusqlite::Result<Self> {
+        let mut record = MovieRecord::default();
+        for (i, field) in fields.iter().enumerate() {
+            match field {
+                Field::Id => record.id = Some(row.get(i)?),
+                Field::Title => record.title = Some(row.get(i)?),
+                Field::Director => record.director = Some(row.get(i)?),
+                Field::ReleaseDate => record.release_date = Some(row.get(i)?),
+            }
+        }
+        Ok(record)
+    }
+
+    /// 字段的文本值，未选中时为 `None`。
+    pub fn get(&self, field: Field) -> Option<String> {
+        match field {
+            Field::Id => self.id.map(|id| id.to_string()),
+            Field::Title => self.title.clone(),
+            Field::Director => self.director.clone(),
+            Field::ReleaseDate => self.release_date.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryStore, MovieFilter, MovieManager, MovieStore};
+
+    #[test]
+    fn test_select_fields() {
+        assert_eq!(
+            Field::parse_list("title, id,title").unwrap(),
+            vec![Field::Title, Field::Id]
+        );
+        assert!(Field::parse_list("id,year").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MovieManager::new(dir.path().join("movies.db").to_str().unwrap()).unwrap();
+        let memory = MemoryStore::new();
+        let movie = Movie::new(
+            1,
+            "Inception".into(),
+            "Christopher Nolan".into(),
+            "2010/7/16".into(),
+        );
+        manager
+            .create_movie(
+                movie.id,
+                movie.title.clone(),
+                movie.director.clone(),
+                movie.release_date.clone(),
+            )
+            .unwrap();
+        memory
+            .create_movie(
+                movie.id,
+                movie.title.clone(),
+                movie.director.clone(),
+                movie.release_date.clone(),
+            )
+            .unwrap();
+
+        // SQLite 后端只查询选中的列，结果与默认实现一致
+        let fields = [Field::ReleaseDate, Field::Title];
+        let filter = MovieFilter::parse(&["nolan"]).unwrap();
+        let records = manager.search_fields(&filter, &fields).unwrap();
+        assert_eq!(records, memory.search_fields(&filter, &fields).unwrap());
+        assert_eq!(
+            records,
+            vec![MovieRecord {
+                title: Some("Inception".into()),
+                release_date: Some("2010/7/16".into()),
+                ..MovieRecord::default()
+            }]
+        );
+        assert_eq!(records[0].get(Field::Id), None);
+        assert_eq!(
+            manager
+                .read_fields(1, &[Field::Id])
+                .unwrap()
+                .unwrap()
+                .get(Field::Id),
+            Some("1".into())
+        );
+        assert!(manager.read_fields(2, &fields).unwrap().is_none());
+    }
+}
@@ -0,0 +1,308 @@
+//! 搜索过滤条件。
+//!
+//! 过滤语言由若干个词组成，词之间是“与”的关系：
+//! - `title=<文本>`：标题或任一别名标题包含该文本（不区分大小写和重音）
+//! - `director=<文本>`：导演的规范名称或任一别名包含该文本（不区分大小写和重音）
+//! - `year=<年份>`：上映年份等于该年份
+//! - `roman=<文本>`：标题、别名标题或导演的拼音（完整或首字母）或假名的罗马字中有单词以该文本开头，见 `romanize` 模块
+//! - `sort=<id|title|director|year>`：结果的排序方式，默认按ID
+//! - 不带 `=` 的词：标题、别名标题或导演包含该文本
+//!
+//! 空的词、空的值（`title=`）和转换后为空的罗马字前缀（`roman=!!!`）会被拒绝，否则它们会匹配所有电影。
+//!
+//! 文本比较使用 `collation` 模块中的折叠规则，排序使用 `UNICODE` 排序规则。
+//! 别名标题和导演别名只保存在 SQLite 中，`matches` 只检查主标题和导演的规范名称。
+
+use std::cmp::Ordering;
+
+use crate::collation::{fold, unicode_cmp};
+use crate::fuzzy::suggest;
+use crate::romanize::{has_word_prefix, romanize, romanize_words};
+use crate::Movie;
+
+/// 过滤语言中可用的字段名。
+const FIELDS: [&str; 5] = ["title", "director", "year", "roman", "sort"];
+
+/// 电影搜索条件，所有字段都为空时匹配全部电影。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MovieFilter {
+    pub text: Option<String>,
+    pub title: Option<String>,
+    pub director: Option<String>,
+    pub year: Option<u32>,
+    /// 罗马字前缀。
+    pub roman: Option<String>,
+    pub sort: SortKey,
+}
+
+/// 搜索结果的排序方式。排序键相同的电影按ID排列。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Id,
+    Title,
+    Director,
+    Year,
+}
+
+impl SortKey {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "id" => Some(SortKey::Id),
+            "title" => Some(SortKey::Title),
+            "director" => Some(SortKey::Director),
+            "year" => Some(SortKey::Year),
+            _ => None,
+        }
+    }
+}
+
+impl MovieFilter {
+    /// 从命令行参数解析过滤条件。
+    pub fn parse<S: AsRef<str>>(terms: &[S]) -> Result<Self, String> {
+        let mut filter = MovieFilter::default();
+        for term in terms {
+            let term = term.as_ref();
+            // 空文本会匹配所有电影，对 `delete --where` 来说尤其危险
+            if term.trim().is_empty() {
+                return Err("empty filter term".to_string());
+            }
+            if let Some((field, value)) = term.split_once('=') {
+                if value.trim().is_empty() {
+                    return Err(format!("empty value for '{}='", field));
+                }
+            }
+            match term.split_once('=') {
+                Some(("title", value)) => filter.title = Some(value.to_string()),
+                Some(("director", value)) => filter.director = Some(value.to_string()),
+                Some(("year", value)) => {
+                    let year = value.parse().map_err(|_| {
+                        format!(
+                            "invalid year '{}': expected a year, for example 2010",
+                            value
+                        )
+                    })?;
+                    filter.year = Some(year);
+                }
+                Some(("roman", value)) => {
+                    if romanize(value).0.is_empty() {
+                        return Err(format!(
+                            "'roman={}' has no letters or digits to match (expected pinyin, romaji, kana or Chinese characters)",
+                            value
+                        ));
+                    }
+                    filter.roman = Some(value.to_string());
+                }
+                Some(("sort", value)) => {
+                    filter.sort = SortKey::parse(value).ok_or_else(|| {
+                        format!(
+                            "invalid sort '{}' (expected id, title, director or year)",
+                            value
+                        )
+                    })?;
+                }
+                Some((field, _)) => {
+                    let mut message = format!("unknown filter field '{}'", field);
+                    if let Some(name) = suggest(field, &FIELDS) {
+                        message.push_str(&format!("; did you mean '{}='?", name));
+                    }
+                    return Err(message);
+                }
+                None => {
+                    filter.text = Some(match filter.text.take() {
+                        Some(text) => format!("{} {}", text, term),
+                        None => term.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    /// 是否含有匹配条件。只有 `sort=` 的过滤条件匹配全部电影，批量更新和删除不接受它。
+    pub fn has_criteria(&self) -> bool {
+        *self
+            != MovieFilter {
+                sort: self.sort,
+                ..MovieFilter::default()
+            }
+    }
+
+    /// 判断一部电影是否满足条件，供非 SQL 的存储实现使用。
+    pub fn matches(&self, movie: &Movie) -> bool {
+        let contains = |haystack: &str, needle: &str| fold(haystack).contains(&fold(needle));
+
+        if let Some(text) = &self.text {
+            if !contains(&movie.title, text) && !contains(&movie.director, text) {
+                return false;
+            }
+        }
+        if let Some(title) = &self.title {
+            if !contains(&movie.title, title) {
+                return false;
+            }
+        }
+        if let Some(director) = &self.director {
+            if !contains(&movie.director, director) {
+                return false;
+            }
+        }
+        if let Some(year) = self.year {
+            if movie.release_date.trim().get(..4) != Some(format!("{:04}", year).as_str()) {
+                return false;
+            }
+        }
+        if let Some(roman) = &self.roman {
+            let prefix = romanize(roman).0;
+            let starts = |text: &str| {
+                let (full, initials) = romanize_words(text);
+                has_word_prefix(&full, &prefix) || has_word_prefix(&initials, &prefix)
+            };
+            if !starts(&movie.title) && !starts(&movie.director) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 生成 SQL 的 WHERE 子句（不含 `WHERE` 关键字）和对应的参数。
+    ///
+    /// 没有匹配条件时返回 `1 = 1`，只用于搜索；批量更新和删除先用 `has_criteria` 检查。
+    pub fn to_sql(&self) -> (String, Vec<String>) {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+
+        // 参数先在 Rust 中折叠，SQL 中只需折叠列的值
+        if let Some(text) = &self.text {
+            params.push(fold(text));
+            clauses.push(format!(
+                "({} OR instr(fold(director), ?{n}) > 0)",
+                title_sql(params.len()),
+                n = params.len()
+            ));
+        }
+        if let Some(title) = &self.title {
+            params.push(fold(title));
+            clauses.push(title_sql(params.len()));
+        }
+        // 先在导演表和别名表中匹配，再通过 director_id 索引找到电影
+        if let Some(director) = &self.director {
+            params.push(fold(director));
+            clauses.push(format!(
+                "(director_id IN (SELECT id FROM directors WHERE instr(fold(name), ?{n}) > 0)
+                  OR director_id IN (SELECT director_id FROM director_aliases
+                                     WHERE instr(alias_key, ?{n}) > 0))",
+                n = params.len()
+            ));
+        }
+        // 与 schema 中的年份表达式索引保持一致，才能用上索引
+        if let Some(year) = self.year {
+            params.push(format!("{:04}", year));
+            clauses.push(format!(
+                "substr(trim(release_date), 1, 4) = ?{}",
+                params.len()
+            ));
+        }
+        // 派生列是以空格分隔的单词后缀，在前面补一个空格后查找 ` <前缀>`，与 `has_word_prefix` 一致
+        if let Some(roman) = &self.roman {
+            params.push(format!(" {}", romanize(roman).0));
+            clauses.push(format!(
+                "(id IN (SELECT id FROM movies
+                         WHERE instr(' ' || title_roman, ?{n}) > 0
+                            OR instr(' ' || title_initials, ?{n}) > 0)
+                  OR id IN (SELECT movie_id FROM alternate_titles
+                         WHERE instr(' ' || title_roman, ?{n}) > 0
+                            OR instr(' ' || title_initials, ?{n}) > 0)
+                  OR director_id IN (SELECT id FROM directors
+                         WHERE instr(' ' || name_roman, ?{n}) > 0
+                            OR instr(' ' || name_initials, ?{n}) > 0))",
+                n = params.len()
+            ));
+        }
+
+        if clauses.is_empty() {
+            ("1 = 1".to_string(), params)
+        } else {
+            (clauses.join(" AND "), params)
+        }
+    }
+
+    /// SQL 的 ORDER BY 子句（不含 `ORDER BY` 关键字）。
+    pub fn order_by(&self) -> &'static str {
+        match self.sort {
+            SortKey::Id => "id",
+            SortKey::Title => "title COLLATE UNICODE, id",
+            SortKey::Director => "director COLLATE UNICODE, id",
+            SortKey::Year => "substr(trim(release_date), 1, 4), id",
+        }
+    }
+
+    /// 按排序方式比较两部电影，供非 SQL 的存储实现使用。
+    pub fn compare(&self, a: &Movie, b: &Movie) -> Ordering {
+        let order = match self.sort {
+            SortKey::Id => Ordering::Equal,
+            SortKey::Title => unicode_cmp(&a.title, &b.title),
+            SortKey::Director => unicode_cmp(&a.director, &b.director),
+            SortKey::Year => {
+                let year = |m: &Movie| m.release_date.trim().get(..4).map(str::to_string);
+                year(a).cmp(&year(b))
+            }
+        };
+        order.then(a.id.cmp(&b.id))
+    }
+}
+
+/// 主标题或任一别名标题包含第 `n` 个参数（已折叠）。
+///
+/// 子串匹配无法使用索引，需要扫描 movies 和 alternate_titles 并逐行折叠（`explain` 可见）。
+fn title_sql(n: usize) -> String {
+    format!(
+        "(instr(fold(title), ?{n}) > 0
+          OR id IN (SELECT movie_id FROM alternate_titles WHERE instr(fold(title), ?{n}) > 0))",
+        n = n
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_match() {
+        let filter = MovieFilter::parse(&["director=nolan", "year=2010"]).unwrap();
+        let inception = Movie::new(
+            1,
+            "Inception".to_string(),
+            "Christopher Nolan".to_string(),
+            "2010/07/16".to_string(),
+        );
+        assert!(filter.matches(&inception));
+        assert!(!MovieFilter::parse(&["year=2014"])
+            .unwrap()
+            .matches(&inception));
+        assert!(MovieFilter::parse(&["incep"]).unwrap().matches(&inception));
+        assert!(MovieFilter::parse(&["rating=5"]).is_err());
+        assert!(MovieFilter::parse(&[""]).is_err());
+        assert!(MovieFilter::parse(&["title= "]).is_err());
+        assert!(MovieFilter::parse(&["titel=x"])
+            .unwrap_err()
+            .ends_with("did you mean 'title='?"));
+        assert!(MovieFilter::parse(&["year=abc"]).is_err());
+        assert!(MovieFilter::parse(&["sort=rating"]).is_err());
+
+        // 不区分大小写和重音
+        let amelie = Movie::new(
+            2,
+            "Le Fabuleux Destin d'Amélie Poulain".to_string(),
+            "Jean-Pierre Jeunet".to_string(),
+            "2001/04/25".to_string(),
+        );
+        assert!(MovieFilter::parse(&["AMELIE"]).unwrap().matches(&amelie));
+        let by_title = MovieFilter::parse(&["sort=title"]).unwrap();
+        assert_eq!(by_title.compare(&inception, &amelie), Ordering::Less);
+        assert_eq!(
+            MovieFilter::default().compare(&inception, &amelie),
+            Ordering::Less
+        );
+    }
+}
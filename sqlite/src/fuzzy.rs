@@ -0,0 +1,208 @@
+//! 容错的模糊搜索。
+//!
+//! 标题、别名标题和导演都会与查询文本比较，得分在 0 到 1 之间，取以下两者中较高的：
+//! - 编辑距离相似度：`1 - 编辑距离 / 较长文本的长度`，适合整体拼错的情况（`Intersteller`）；
+//!   相邻两个字符互换（`lsit`）也只算一次编辑；
+//! - 三元组相似度：两段文本的字符三元组的 Dice 系数，适合只输入了部分单词的情况（`dark knight`）。
+//!
+//! 比较前文本先按 `collation` 模块的规则折叠大小写和重音，并把连续空白合并为一个空格。
+//!
+//! `suggest` 用同样的编辑距离从一组名称（命令、字段等）中找出最接近的一个，用于“did you mean”提示。
+
+use std::collections::HashSet;
+
+use crate::collation::fold;
+use crate::{AlternateTitle, Movie, MovieManager, Result};
+
+/// 低于这个得分的电影不会出现在结果中。
+pub const MIN_SCORE: f64 = 0.4;
+
+/// 模糊搜索的一个结果。
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub movie: Movie,
+    /// 相似度，1 表示折叠后完全相同。
+    pub score: f64,
+    /// 得分最高的文本：标题、某个别名标题或导演。
+    pub matched: String,
+}
+
+impl FuzzyMatch {
+    /// 查询是否与标题（或别名标题）折叠后完全相同。
+    pub fn is_exact(&self) -> bool {
+        self.score >= 1.0 && self.matched != self.movie.director
+    }
+}
+
+/// 查询与一段文本的相似度。
+pub fn similarity(query: &str, text: &str) -> f64 {
+    let (query, text) = (normalize(query), normalize(text));
+    if query.is_empty() || text.is_empty() {
+        return 0.0;
+    }
+    if query == text {
+        return 1.0;
+    }
+    let (a, b): (Vec<char>, Vec<char>) = (query.chars().collect(), text.chars().collect());
+    let edit = 1.0 - edit_distance(&a, &b) as f64 / a.len().max(b.len()) as f64;
+    edit.max(trigram_similarity(&a, &b))
+}
+
+/// 折叠大小写和重音，合并空白。
+fn normalize(text: &str) -> String {
+    fold(text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 两段文本的编辑距离：插入、删除、替换和相邻字符互换各算一次。
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    // d[i][j] 是 a 的前 i 个字符与 b 的前 j 个字符之间的距离
+    let mut d: Vec<Vec<usize>> = (0..=a.len())
+        .map(|i| (0..=b.len()).map(|j| if i == 0 { j } else { i }).collect())
+        .collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j - 1] + cost)
+                .min(d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// 从候选名称中找出与输入最接近的一个，用于“did you mean”提示。
+///
+/// 编辑距离不超过输入长度的三分之一（至少为 1）才算接近；距离相同时取靠前的候选。
+pub fn suggest<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let input: Vec<char> = input.to_lowercase().chars().collect();
+    let max_distance = (input.len() / 3).max(1);
+    candidates
+        .iter()
+        .map(|&candidate| {
+            let chars: Vec<char> = candidate.chars().collect();
+            (edit_distance(&input, &chars), candidate)
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// 字符三元组的 Dice 系数。开头补两个空格、结尾补一个，短文本和词首也能产生三元组。
+fn trigram_similarity(a: &[char], b: &[char]) -> f64 {
+    let trigrams = |chars: &[char]| -> HashSet<[char; 3]> {
+        let padded: Vec<char> = [' ', ' ']
+            .into_iter()
+            .chain(chars.iter().copied())
+            .chain([' '])
+            .collect();
+        padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+    };
+    let (a, b) = (trigrams(a), trigrams(b));
+    let common = a.intersection(&b).count();
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// 为每部电影打分，返回得分最高的 `limit` 部，得分相同时按ID排列。
+pub fn rank(
+    movies: Vec<Movie>,
+    alternates: &[AlternateTitle],
+    query: &str,
+    limit: usize,
+) -> Vec<FuzzyMatch> {
+    let mut matches: Vec<FuzzyMatch> =
+        movies
+            .into_iter()
+            .filter_map(|movie| {
+                let texts = [movie.title.as_str(), movie.director.as_str()]
+                    .into_iter()
+                    .chain(
+                        alternates
+                            .iter()
+                            .filter(|alt| alt.movie_id == movie.id)
+                            .map(|alt| alt.title.as_str()),
+                    );
+                let (score, matched) = texts.map(|text| (similarity(query, text), text)).fold(
+                    (0.0, ""),
+                    |best, next| if next.0 > best.0 { next } else { best },
+                );
+                let matched = matched.to_string();
+                (score >= MIN_SCORE).then_some(FuzzyMatch {
+                    movie,
+                    score,
+                    matched,
+                })
+            })
+            .collect();
+    matches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.movie.id.cmp(&b.movie.id))
+    });
+    matches.truncate(limit);
+    matches
+}
+
+impl MovieManager {
+    /// 按相似度搜索电影，包括别名标题，返回得分最高的 `limit` 部。
+    ///
+    /// 需要读取全部电影在内存中打分，适合命令行规模的目录。
+    pub fn fuzzy_search(&self, query: &str, limit: usize) -> Result<Vec<FuzzyMatch>> {
+        let alternates = self.all_alternate_titles()?;
+        Ok(rank(self.list_movies()?, &alternates, query, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TitleKind;
+
+    #[test]
+    fn test_fuzzy_search() {
+        assert_eq!(similarity("AMÉLIE", "amelie"), 1.0);
+        assert!(similarity("Intersteller", "Interstellar") > 0.9);
+        assert!(similarity("dark knight", "The Dark Knight") > 0.7);
+        assert!(similarity("Inception", "Tenet") < MIN_SCORE);
+        let commands = ["list", "delete", "search", "director"];
+        assert_eq!(suggest("lsit", &commands), Some("list"));
+        assert_eq!(suggest("SERCH", &commands), Some("search"));
+        assert_eq!(suggest("directr", &commands), Some("director"));
+        assert_eq!(suggest("xyz", &commands), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MovieManager::new(dir.path().join("movies.db").to_str().unwrap()).unwrap();
+        for (id, title, year) in [
+            (1, "Interstellar", "2014/11/5"),
+            (2, "Inception", "2010/7/16"),
+            (3, "The Dark Knight", "2008/7/18"),
+            (4, "Insomnia", "2002/5/24"),
+        ] {
+            manager
+                .create_movie(id, title.into(), "Christopher Nolan".into(), year.into())
+                .unwrap();
+        }
+        manager
+            .add_alternate_title(3, "Il cavaliere oscuro", "it", TitleKind::Localized)
+            .unwrap();
+
+        let found = manager.fuzzy_search("Intersteller", 5).unwrap();
+        assert_eq!(found[0].movie.id, 1);
+        assert!(!found[0].is_exact());
+        assert!(found.iter().all(|m| m.score >= MIN_SCORE));
+        assert_eq!(
+            manager.fuzzy_search("dark knight", 1).unwrap()[0].movie.id,
+            3
+        );
+
+        // 别名标题和导演同样参与打分，导演完全相同不算标题的精确匹配
+        let found = manager.fuzzy_search("cavaliere oscuro", 1).unwrap();
+        assert_eq!(found[0].matched, "Il cavaliere oscuro");
+        let found = manager.fuzzy_search("christopher nolan", 10).unwrap();
+        assert_eq!(found.len(), 4);
+        assert!(found.iter().all(|m| m.score == 1.0 && !m.is_exact()));
+        assert!(manager.fuzzy_search("inception", 1).unwrap()[0].is_exact());
+    }
+}
@@ -0,0 +1,85 @@
+//! 从 CSV 文件导入电影。
+//!
+//! CSV 需要包含 `id,title,director,release_date` 表头（与 `data/movies.csv` 相同）。
+//! 每一行都通过 `MovieStore::create_movie` 写入，因此与手动创建一样经过字段校验；
+//! 校验失败或ID冲突的行会被跳过并记录在报告中，不会中断整个导入。
+//! 其他格式（例如 xlsx）的导入也使用这里的报告和逐行处理。
+
+#[cfg(feature = "csv")]
+use std::io::Read;
+
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+use crate::{Movie, MovieStore};
+
+/// 导入结果。
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// 成功导入的电影数量。
+    pub imported: usize,
+    /// 被跳过的行：行号（表头为第 1 行）和原因。
+    pub failures: Vec<(u64, String)>,
+}
+
+#[cfg(any(feature = "csv", feature = "xlsx"))]
+impl ImportReport {
+    /// 写入一行解析出的电影；解析或写入失败时记录行号和原因。
+    pub(crate) fn import_row<S: MovieStore>(
+        &mut self,
+        store: &S,
+        line: u64,
+        parsed: Result<Movie, String>,
+    ) {
+        let result = parsed.and_then(|movie| {
+            store
+                .create_movie(movie.id, movie.title, movie.director, movie.release_date)
+                .map_err(|e| e.to_string())
+        });
+        match result {
+            Ok(()) => self.imported += 1,
+            Err(reason) => self.failures.push((line, reason)),
+        }
+    }
+}
+
+/// 从 CSV 读取电影并写入存储。
+#[cfg(feature = "csv")]
+pub fn import_csv<S: MovieStore, R: Read>(
+    store: &S,
+    reader: R,
+) -> Result<ImportReport, csv::Error> {
+    let mut report = ImportReport::default();
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+
+    for record in csv_reader.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let movie = record
+            .deserialize::<Movie>(Some(&headers))
+            .map_err(|e| e.to_string());
+        report.import_row(store, line, movie);
+    }
+
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use crate::MemoryStore;
+
+    #[test]
+    fn test_import_skips_invalid_rows() {
+        let csv = "id,title,director,release_date\n\
+                   1,Inception,Christopher Nolan,2010/7/16\n\
+                   2,,Christopher Nolan,2014/11/5\n\
+                   1,Duplicate,Someone,2001/1/1\n\
+                   x,Bad Id,Someone,2001/1/1\n";
+        let store = MemoryStore::new();
+        let report = import_csv(&store, csv.as_bytes()).unwrap();
+
+        assert_eq!(report.imported, 1);
+        let lines: Vec<u64> = report.failures.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+    }
+}
@@ -1,7 +1,58 @@
-use rusqlite::{params, Connection, Result};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, trace};
+
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod changes;
+pub mod collation;
+pub mod directors;
+pub mod doctor;
+#[cfg(feature = "toml")]
+pub mod dump;
+pub mod error;
+pub mod fields;
+pub mod filter;
+pub mod fuzzy;
+pub mod import;
+pub mod pool;
+pub mod romanize;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod store;
+pub mod titles;
+pub mod transaction;
+pub mod validation;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+#[cfg(feature = "async")]
+pub use asynchronous::AsyncMovieManager;
+pub use changes::{ChangeEvent, ChangeKind};
+pub use directors::Director;
+pub use doctor::{HealthReport, RepairReport};
+#[cfg(feature = "toml")]
+pub use dump::{Dump, DumpDirector};
+pub use error::{Error, Result};
+pub use fields::{Field, MovieRecord};
+pub use filter::{MovieFilter, SortKey};
+pub use fuzzy::FuzzyMatch;
+#[cfg(feature = "csv")]
+pub use import::import_csv;
+pub use import::ImportReport;
+pub use pool::{PoolConfig, PooledMovieManager};
+pub use store::{MemoryStore, MovieStore};
+pub use titles::{AlternateTitle, TitleKind};
+pub use transaction::ChangeSet;
+pub use validation::{FieldError, ValidationError, ValidationRules};
+#[cfg(feature = "xlsx")]
+pub use xlsx::{import_xlsx, XlsxError};
 
 /// 电影结构体，表示一部电影的记录。
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Movie {
     pub id: u32,
     pub title: String,
@@ -20,64 +71,166 @@ impl Movie {
             release_date,
         }
     }
+
+    /// 把各个文本字段转换为 NFC 形式，写入前调用。
+    pub fn normalized(self) -> Self {
+        Self {
+            id: self.id,
+            title: collation::nfc(&self.title),
+            director: collation::nfc(&self.director),
+            release_date: collation::nfc(&self.release_date),
+        }
+    }
+
+    /// 自然键：去掉首尾空白并折叠大小写和重音（`collation::fold`）的标题、导演和上映年份，
+    /// 用于判断两条记录是否是同一部电影。SQLite 中标题的折叠结果保存在 `title_key` 列。
+    pub fn natural_key(&self) -> (String, String, String) {
+        (
+            collation::fold(self.title.trim()),
+            collation::fold(self.director.trim()),
+            self.release_date.trim().chars().take(4).collect(),
+        )
+    }
+
+    /// 从查询结果行（id, title, director, release_date）构造电影。
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            director: row.get(2)?,
+            release_date: row.get(3)?,
+        })
+    }
+}
+
+/// `upsert_movie` 的结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    /// 插入了一条新记录。
+    Inserted,
+    /// 更新了已有的记录（按ID或自然键匹配），附带该记录的ID。
+    Updated(u32),
+}
+
+/// 自然键唯一索引的名称。
+pub(crate) const NATURAL_KEY_INDEX: &str = "movies_natural_key";
+
+/// 创建自然键唯一索引的 SQL。导演按ID比较，因此别名也视为同一位导演；
+/// 标题使用 Rust 中折叠后写入的 `title_key`，SQLite 的 `lower()` 只能处理 ASCII。
+pub(crate) fn natural_key_index_sql() -> String {
+    format!(
+        "CREATE UNIQUE INDEX IF NOT EXISTS {} ON movies (
+            title_key, director_id, substr(trim(release_date), 1, 4)
+        )",
+        NATURAL_KEY_INDEX
+    )
 }
 
 /// 电影管理器，用于管理电影的CRUD操作。
 pub struct MovieManager {
     conn: Connection,
+    rules: ValidationRules,
+}
+
+/// 查询完整电影时的列。
+const MOVIE_COLUMNS: &str = "id, title, director, release_date";
+
+/// 生成搜索使用的 SQL 语句和参数，只查询 `columns` 中的列。
+fn search_sql(filter: &MovieFilter, columns: &str) -> (String, Vec<String>) {
+    let (clause, values) = filter.to_sql();
+    let sql = format!(
+        "SELECT {} FROM movie_details WHERE {} ORDER BY {}",
+        columns,
+        clause,
+        filter.order_by()
+    );
+    (sql, values)
+}
+
+/// 字段列表对应的列，用逗号分隔。
+fn field_columns(fields: &[Field]) -> String {
+    fields.iter().map(|f| f.name()).collect::<Vec<_>>().join(", ")
+}
+
+/// SQLite 每执行完一条语句回调一次，在 trace 级别记录语句及其耗时。
+fn log_statement(sql: &str, elapsed: Duration) {
+    trace!(target: "sqlite_movies::sql", elapsed_us = elapsed.as_micros() as u64, "{}", sql);
 }
 
 impl MovieManager {
     /// 创建一个新的电影管理器，并初始化数据库连接。
     pub fn new(db_path: &str) -> Result<Self> {
+        debug!(path = db_path, "opening database");
         let conn = Connection::open(db_path)?;
-        let manager = Self { conn };
+        Self::from_connection(conn)
+    }
+
+    /// 使用已打开的连接创建电影管理器，并初始化数据库。
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        let manager = Self::read_only(conn)?;
         manager.initialize_database()?;
         Ok(manager)
     }
 
-    /// 初始化数据库，创建movies表（如果不存在）。
+    /// 包装一个只读连接，只注册排序规则和函数（`collation`、`romanize`），不做初始化。
+    pub(crate) fn read_only(mut conn: Connection) -> Result<Self> {
+        conn.profile(Some(log_statement));
+        collation::register(&conn)?;
+        romanize::register(&conn)?;
+        Ok(Self {
+            conn,
+            rules: ValidationRules::default(),
+        })
+    }
+
+    /// 设置创建和更新时使用的校验规则。
+    pub fn with_rules(mut self, rules: ValidationRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// 初始化数据库：开启外键约束，创建表并执行尚未应用的迁移。
     fn initialize_database(&self) -> Result<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS movies (
-                id INTEGER PRIMARY KEY,
-                title TEXT NOT NULL,
-                director TEXT NOT NULL,
-                release_date TEXT NOT NULL
-            )",
-            [],
-        )?;
-        Ok(())
+        self.conn.pragma_update(None, "foreign_keys", true)?;
+        schema::migrate(&self.conn)
     }
 
     /// 添加一部新的电影到数据库。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     pub fn create_movie(&self, id: u32, title: String, director: String, release_date: String) -> Result<()> {
+        let movie = Movie::new(id, title, director, release_date).normalized();
+        movie.validate(&self.rules)?;
+        self.transaction(|manager| {
+            let director_id = manager.director_id(&movie.director)?;
+            manager.insert_movie(&movie, director_id)
+        })
+    }
+
+    /// 插入一部已经规范化并通过校验的电影，导演已解析为 `director_id`。
+    pub(crate) fn insert_movie(&self, movie: &Movie, director_id: u32) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO movies (id, title, director, release_date) VALUES (?1, ?2, ?3, ?4)",
-            params![id, title, director, release_date],
+            "INSERT INTO movies (id, title, director_id, release_date, title_key, title_roman, title_initials)
+             VALUES (?1, ?2, ?3, ?4, ?5, romanize(?2), romanize_initials(?2))",
+            params![movie.id, movie.title, director_id, movie.release_date, movie.natural_key().0],
         )?;
         Ok(())
     }
 
     /// 根据ID读取一部电影的信息。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     pub fn read_movie(&self, id: u32) -> Result<Option<Movie>> {
-        let mut stmt = self.conn.prepare("SELECT id, title, director, release_date FROM movies WHERE id = ?1")?;
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM movie_details WHERE id = ?1", MOVIE_COLUMNS))?;
         let mut rows = stmt.query(params![id])?;
 
         if let Some(row) = rows.next()? {
-            let movie = Movie {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                director: row.get(2)?,
-                release_date: row.get(3)?,
-            };
-            Ok(Some(movie))
+            Ok(Some(Movie::from_row(row)?))
         } else {
             Ok(None)
         }
     }
 
-    /// 更新一部电影的信息。
+    /// 更新一部电影的信息。更新后的电影整体需要通过校验。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     pub fn update_movie(
         &self,
         id: u32,
@@ -85,48 +238,232 @@ impl MovieManager {
         director: Option<String>,
         release_date: Option<String>,
     ) -> Result<bool> {
-        let exists = self.read_movie(id)?.is_some();
-        if !exists {
-            return Ok(false);
-        }
+        let mut movie = match self.read_movie(id)? {
+            Some(movie) => movie,
+            None => return Ok(false),
+        };
 
         if let Some(title) = title {
-            self.conn.execute("UPDATE movies SET title = ?1 WHERE id = ?2", params![title, id])?;
+            movie.title = title;
         }
         if let Some(director) = director {
-            self.conn.execute("UPDATE movies SET director = ?1 WHERE id = ?2", params![director, id])?;
+            movie.director = director;
         }
         if let Some(release_date) = release_date {
-            self.conn.execute("UPDATE movies SET release_date = ?1 WHERE id = ?2", params![release_date, id])?;
+            movie.release_date = release_date;
+        }
+        let movie = movie.normalized();
+        movie.validate(&self.rules)?;
+
+        self.transaction(|manager| {
+            let director_id = manager.director_id(&movie.director)?;
+            manager.conn.execute(
+                "UPDATE movies SET title = ?1, director_id = ?2, release_date = ?3,
+                 title_key = ?5,
+                 title_roman = romanize(?1), title_initials = romanize_initials(?1) WHERE id = ?4",
+                params![movie.title, director_id, movie.release_date, id, movie.natural_key().0],
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// 插入一部电影，或更新按ID或自然键匹配到的已有电影。
+    ///
+    /// 先按ID匹配；ID不存在时再按自然键（标题、导演、上映年份）匹配，
+    /// 匹配到的记录保留原来的ID。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn upsert_movie(&self, movie: Movie) -> Result<UpsertOutcome> {
+        let movie = movie.normalized();
+        movie.validate(&self.rules)?;
+        self.transaction(|manager| {
+            let existing = match manager.read_movie(movie.id)? {
+                Some(found) => Some(found.id),
+                None => manager.find_by_natural_key(&movie)?,
+            };
+            let director_id = manager.director_id(&movie.director)?;
+            match existing {
+                Some(id) => {
+                    manager.conn.execute(
+                        "UPDATE movies SET title = ?1, director_id = ?2, release_date = ?3,
+                 title_key = ?5,
+                 title_roman = romanize(?1), title_initials = romanize_initials(?1) WHERE id = ?4",
+                        params![movie.title, director_id, movie.release_date, id, movie.natural_key().0],
+                    )?;
+                    Ok(UpsertOutcome::Updated(id))
+                }
+                None => {
+                    manager.insert_movie(&movie, director_id)?;
+                    Ok(UpsertOutcome::Inserted)
+                }
+            }
+        })
+    }
+
+    /// 按自然键查找电影的ID。
+    fn find_by_natural_key(&self, movie: &Movie) -> Result<Option<u32>> {
+        let director_id = match self.find_director_id(&movie.director)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let (title, _, year) = movie.natural_key();
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM movies
+             WHERE title_key = ?1 AND director_id = ?2
+               AND substr(trim(release_date), 1, 4) = ?3
+             ORDER BY id LIMIT 1",
+        )?;
+        let mut rows = stmt.query(params![title, director_id, year])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
         }
-        Ok(true)
+    }
+
+    /// 开启或关闭自然键唯一约束。
+    ///
+    /// 约束以唯一索引的形式保存在数据库中，开启后对所有连接生效；
+    /// 如果已经存在重复的电影，开启会失败。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn set_natural_key_unique(&self, enabled: bool) -> Result<()> {
+        if enabled {
+            self.conn.execute(&natural_key_index_sql(), [])?;
+        } else {
+            self.conn
+                .execute(&format!("DROP INDEX IF EXISTS {}", NATURAL_KEY_INDEX), [])?;
+        }
+        Ok(())
+    }
+
+    /// 自然键唯一约束是否已开启。
+    pub fn natural_key_unique(&self) -> Result<bool> {
+        let count: u32 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?1",
+            params![NATURAL_KEY_INDEX],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
     }
 
     /// 删除一部电影。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     pub fn delete_movie(&self, id: u32) -> Result<bool> {
         let affected = self.conn.execute("DELETE FROM movies WHERE id = ?1", params![id])?;
         Ok(affected > 0)
     }
 
+    /// 在一个事务中更新所有满足条件的电影，返回更新的数量。
+    ///
+    /// 每部电影更新后都需要通过校验，任何一部失败都会回滚全部修改。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn update_where(
+        &self,
+        filter: &MovieFilter,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<usize> {
+        // 没有匹配条件时 to_sql 会匹配全部电影
+        if !filter.has_criteria() {
+            return Ok(0);
+        }
+        self.transaction(|manager| {
+            let movies = manager.search_movies(filter)?;
+            for movie in &movies {
+                manager.update_movie(movie.id, title.clone(), director.clone(), release_date.clone())?;
+            }
+            Ok(movies.len())
+        })
+    }
+
+    /// 删除所有满足条件的电影，返回删除的数量。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn delete_where(&self, filter: &MovieFilter) -> Result<usize> {
+        if !filter.has_criteria() {
+            return Ok(0);
+        }
+        let (clause, values) = filter.to_sql();
+        let affected = self.conn.execute(
+            &format!(
+                "DELETE FROM movies WHERE id IN (SELECT id FROM movie_details WHERE {})",
+                clause
+            ),
+            rusqlite::params_from_iter(values),
+        )?;
+        Ok(affected)
+    }
+
     /// 列出所有电影。
-    pub fn list_movies(&self) -> Result<()> {
-        let mut stmt = self.conn.prepare("SELECT id, title, director, release_date FROM movies")?;
-        let movie_iter = stmt.query_map([], |row| {
-            Ok(Movie {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                director: row.get(2)?,
-                release_date: row.get(3)?,
-            })
-        })?;
-
-        for movie in movie_iter {
-            let m = movie?;
-            println!(
-                "ID: {}, Title: {}, Director: {}, Release Date: {}",
-                m.id, m.title, m.director, m.release_date
-            );
+    pub fn list_movies(&self) -> Result<Vec<Movie>> {
+        self.search_movies(&MovieFilter::default())
+    }
+
+    /// 搜索满足条件的电影。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn search_movies(&self, filter: &MovieFilter) -> Result<Vec<Movie>> {
+        let (sql, values) = search_sql(filter, MOVIE_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let movies = stmt
+            .query_map(rusqlite::params_from_iter(values), Movie::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(movies)
+    }
+
+    /// ID与 `id` 最接近的至多 `limit` 部电影，按距离再按ID排列。
+    pub fn nearest_movies(&self, id: u32, limit: usize) -> Result<Vec<Movie>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM movie_details ORDER BY abs(id - ?1), id LIMIT ?2",
+            MOVIE_COLUMNS
+        ))?;
+        let movies = stmt
+            .query_map(params![id, limit as i64], Movie::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(movies)
+    }
+
+    /// 根据ID读取一部电影的部分字段，只查询对应的列。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn read_fields(&self, id: u32, fields: &[Field]) -> Result<Option<MovieRecord>> {
+        let sql = format!("SELECT {} FROM movie_details WHERE id = ?1", field_columns(fields));
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(params![id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(MovieRecord::from_row(row, fields)?)),
+            None => Ok(None),
         }
-        Ok(())
+    }
+
+    /// 搜索满足条件的电影，只查询选中字段对应的列。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn search_fields(&self, filter: &MovieFilter, fields: &[Field]) -> Result<Vec<MovieRecord>> {
+        let (sql, values) = search_sql(filter, &field_columns(fields));
+        let mut stmt = self.conn.prepare(&sql)?;
+        let records = stmt
+            .query_map(rusqlite::params_from_iter(values), |row| MovieRecord::from_row(row, fields))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(records)
+    }
+
+    /// 返回搜索会执行的 SQL 语句、参数，以及 SQLite 的查询计划（`EXPLAIN QUERY PLAN`）。
+    ///
+    /// 查询计划每行一个步骤，子步骤按层级缩进两个空格。
+    pub fn explain_search(&self, filter: &MovieFilter) -> Result<(String, Vec<String>, Vec<String>)> {
+        let (sql, values) = search_sql(filter, MOVIE_COLUMNS);
+        let mut stmt = self.conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+        let steps = stmt
+            .query_map(rusqlite::params_from_iter(&values), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(3)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut depths = std::collections::HashMap::new();
+        let plan = steps
+            .into_iter()
+            .map(|(id, parent, detail)| {
+                let depth = depths.get(&parent).map_or(0, |d| d + 1);
+                depths.insert(id, depth);
+                format!("{}{}", "  ".repeat(depth), detail)
+            })
+            .collect();
+        Ok((sql, values, plan))
     }
 }
@@ -1,131 +1,1132 @@
+mod cli_error;
+mod template;
+#[cfg(feature = "tui")]
+mod tui;
+
 use std::env;
 use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
 use std::process;
-use sqlite::MovieManager;
+use std::time::Duration;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+use cli_error::{parse_id, CliError, OutputFormat, ErrorKind, LineError, Suggestion};
+use template::MovieTemplate;
+use sqlite_movies::{doctor, fuzzy};
+use sqlite_movies::schema::SCHEMA_VERSION;
+use sqlite_movies::server::MovieServer;
+use sqlite_movies::{import_csv, import_xlsx, ChangeSet, Dump, Field, Movie, FuzzyMatch, MovieFilter, MovieManager, MovieRecord, MovieStore, TitleKind, UpsertOutcome, ValidationRules};
+
+/// 数据库文件。
+const DATABASE: &str = "movies.db";
+
+/// 批量更新或删除超过这个数量时需要确认。
+const CONFIRM_THRESHOLD: usize = 10;
+
+/// 模糊搜索默认返回的数量，也是按标题选择电影时列出的候选数量。
+const FUZZY_TOP: usize = 5;
+
+/// 全局选项。
+struct Options {
+    dry_run: bool,
+    /// 日志详细程度：-1 为 `-q`，0 为默认，1 为 `-v`，2 及以上为 `-vv`。
+    verbosity: i8,
+    error_format: OutputFormat,
+    log_format: OutputFormat,
+    /// read、list 和 search 的输出模板，没有时使用默认格式。
+    template: Option<MovieTemplate>,
+    /// `--fields` 选择的字段，没有时输出全部字段。
+    fields: Option<Vec<Field>>,
+    /// `--lang` 指定的首选语言，read、list 和 search 按它显示别名标题。
+    lang: Option<String>,
+}
+
+impl Options {
+    /// 要输出的字段。
+    fn fields(&self) -> &[Field] {
+        self.fields.as_deref().unwrap_or(&Field::ALL)
+    }
+
+    /// 打印写操作的成功提示。试运行不会写入，改由 `print_changes` 列出将要发生的变化。
+    fn report(&self, message: impl std::fmt::Display) {
+        if !self.dry_run {
+            println!("{}", message);
+        }
+    }
+}
 
 /// 枚举，表示可用的命令。
+#[derive(Clone, Copy)]
 enum Command {
     Create,
     Read,
     Update,
+    Upsert,
     Delete,
     List,
+    Search,
+    Explain,
+    Dump,
+    Load,
+    Import,
+    Export,
+    Batch,
+    Director,
+    Title,
+    NaturalKey,
+    Serve,
+    Tui,
+    Doctor,
+    Watch,
     Help,
 }
 
 impl Command {
+    /// 全部命令的名称，用于在输错时提示最接近的命令。
+    const NAMES: [&'static str; 21] = [
+        "create", "read", "update", "upsert", "delete", "list", "search", "explain", "dump", "load", "import",
+        "export", "batch", "director", "title", "natural-key", "serve", "tui", "doctor", "watch", "help",
+    ];
+
     /// 将字符串转换为命令枚举。
     fn from_str(input: &str) -> Option<Command> {
         match input.to_lowercase().as_str() {
             "create" => Some(Command::Create),
             "read" => Some(Command::Read),
             "update" => Some(Command::Update),
+            "upsert" => Some(Command::Upsert),
             "delete" => Some(Command::Delete),
             "list" => Some(Command::List),
+            "search" => Some(Command::Search),
+            "explain" => Some(Command::Explain),
+            "dump" => Some(Command::Dump),
+            "load" => Some(Command::Load),
+            "import" => Some(Command::Import),
+            "export" => Some(Command::Export),
+            "batch" => Some(Command::Batch),
+            "director" => Some(Command::Director),
+            "title" => Some(Command::Title),
+            "natural-key" => Some(Command::NaturalKey),
+            "serve" => Some(Command::Serve),
+            "tui" => Some(Command::Tui),
+            "doctor" => Some(Command::Doctor),
+            "watch" => Some(Command::Watch),
             "help" => Some(Command::Help),
             _ => None,
         }
     }
 }
 
+/// 未知命令的用法错误，附带最接近的命令。
+fn unknown_command(input: &str, names: &[&str]) -> CliError {
+    let suggestions = fuzzy::suggest(input, names).map(|name| Suggestion::Name(name.to_string())).into_iter().collect();
+    CliError::usage(format!("unknown command '{}'", input)).with_suggestions(suggestions)
+}
+
+/// 电影不存在的错误，附带ID最接近的几部电影。
+fn movie_not_found<S: MovieStore>(store: &S, id: u32) -> CliError {
+    // 建议只是辅助信息，读取失败时不附带
+    let suggestions = store
+        .nearest_movies(id, 3)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|m| Suggestion::Movie { id: m.id, title: m.title })
+        .collect();
+    CliError::movie_not_found(id).with_suggestions(suggestions)
+}
+
 /// 打印帮助信息。
 fn print_help() {
     println!("Usage:");
-    println!("    cargo run -- <command> [arguments]");
+    println!("    cargo run -- [options] <command> [arguments]");
+    println!();
+    println!("Options (before the command; `--` also ends the options):");
+    println!("    --dry-run                  run mutating commands in a transaction that is rolled back,");
+    println!("                               and report the rows that would change");
+    println!("    --error-format text|json   how errors are written to stderr (default text)");
+    println!("    -v, -vv, -q                log operations (-v), SQL statements (-vv) or only errors (-q)");
+    println!("    --log-format text|json     how logs are written to stderr (default text);");
+    println!("                               RUST_LOG adds filters such as sqlite_movies::sql=trace");
+    println!("    --fields <field,...>       only output these fields in read, list, search and export;");
+    println!("                               id, title, director and release_date");
+    println!("    --template <template>      format read, list and search output with a Jinja template,");
+    println!("                               e.g. '- {{{{ title }}}} ({{{{ release_date | date(\"%Y\") }}}})'");
+    println!("    --template-file <file>     read the template from a file; it may define header, row");
+    println!("                               and footer blocks");
+    println!("    --lang <code>              show titles in this language in read, list and search,");
+    println!("                               e.g. ja or zh-TW; movies without one keep their title");
     println!();
     println!("Commands:");
     println!("    create <id> <title> <director> <release_date>");
-    println!("    read <id>");
-    println!("    update <id> [title] [director] [release_date]");
-    println!("    delete <id>");
-    println!("    list");
+    println!("    read <id|title>");
+    println!("    update <id|title> [title] [director] [release_date]");
+    println!("    update --where <filter>... --set <field=value>... [--yes]");
+    println!("    upsert <id> <title> <director> <release_date>");
+    println!("    delete <id|title>");
+    println!("                               (a title is matched fuzzily; pick one if several match)");
+    println!("    delete --where <filter>... [--yes]");
+    println!("    list [sort=<id|title|director|year>]");
+    println!("    search [text] [title=<text>] [director=<text>] [year=<yyyy>] [roman=<pinyin>] [sort=<key>]");
+    println!("                               (roman= matches pinyin, initials or kana romaji from the start of any word)");
+    println!("                               (kanji-only Japanese text matches its pinyin; romaji covers kana only)");
+    println!("                               (text matching ignores case and accents)");
+    println!("    search --fuzzy [--top <n>] <text>");
+    println!("                               (best matches by similarity, tolerating typos)");
+    println!("    explain [search filters]   (show the SQLite query plan of a search)");
+    println!("    import <file.csv|file.xlsx>");
+    println!("                               (columns: id,title,director,release_date)");
+    println!("    export <file.xlsx>         (write all movies to a spreadsheet)");
+    println!("    batch <file>               (one create/update/upsert/delete per line)");
+    println!("    dump [file]                (write the catalog as sorted TOML; default stdout)");
+    println!("    load <file|->              (replace the catalog with a dump)");
+    println!("    director list");
+    println!("    director rename <name> <new_name>");
+    println!("    director merge <from> <into>");
+    println!("    director alias <name> <alias>");
+    println!("    title list <id>            (alternate titles of a movie)");
+    println!("    title add <id> <lang> <original|localized|working> <title>");
+    println!("    title remove <id> <title>");
+    println!("    natural-key [on|off]       (unique title + director + year)");
+    println!("    serve [--bind <addr>]      (default 127.0.0.1:8080)");
+    println!("    tui                        (browse, search and edit in the terminal)");
+    println!("    doctor [--fix]             (check the database; --fix repairs what it can)");
+    println!("    watch [--since <seq>] [--interval <ms>]");
+    println!("                               (stream changes as NDJSON, including other processes)");
     println!("    help");
+    println!();
+    println!("Exit codes:");
+    println!("    0 success, 1 other error, 2 usage error, 3 invalid input,");
+    println!("    4 not found, 5 conflict, 6 storage error");
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // 收集命令行参数
-    let args: Vec<String> = env::args().collect();
+/// 打印电影列表，指定了模板时按模板输出。
+fn print_records(records: &[MovieRecord], options: &Options) -> Result<(), CliError> {
+    if let Some(template) = &options.template {
+        return template.print(records);
+    }
+    for record in records {
+        println!("{}", record_line(record, options));
+    }
+    Ok(())
+}
 
+/// 一部电影的默认文本格式：选中的字段用逗号分隔。
+fn record_line(record: &MovieRecord, options: &Options) -> String {
+    let line: Vec<String> = options
+        .fields()
+        .iter()
+        .map(|&field| format!("{}: {}", field.label(), record.get(field).unwrap_or_default()))
+        .collect();
+    line.join(", ")
+}
+
+/// 查询要输出的记录；指定了 `--lang` 时把标题换成该语言的别名标题。
+fn query_records<S: MovieStore>(
+    store: &S,
+    options: &Options,
+    query: impl FnOnce(&[Field]) -> Result<Vec<MovieRecord>, S::Error>,
+) -> Result<Vec<MovieRecord>, S::Error> {
+    let lang = match &options.lang {
+        Some(lang) if options.fields().contains(&Field::Title) => lang,
+        _ => return query(options.fields()),
+    };
+    // 需要ID才能找到别名标题，未选中ID时查询后再去掉
+    let mut fields = options.fields().to_vec();
+    let with_id = fields.contains(&Field::Id);
+    if !with_id {
+        fields.push(Field::Id);
+    }
+    let mut records = query(&fields)?;
+    let titles = store.localized_titles(lang)?;
+    for record in &mut records {
+        if let Some(title) = record.id.and_then(|id| titles.get(&id)) {
+            record.title = Some(title.clone());
+        }
+        if !with_id {
+            record.id = None;
+        }
+    }
+    Ok(records)
+}
+
+/// 打印一部电影。
+fn print_movie(movie: &Movie) {
+    println!(
+        "ID: {}, Title: {}, Director: {}, Release Date: {}",
+        movie.id, movie.title, movie.director, movie.release_date
+    );
+}
+
+fn main() {
+    // 收集命令行参数，并取出全局选项
+    let mut args: Vec<String> = env::args().collect();
+    let options = match take_options(&mut args) {
+        Ok(options) => options,
+        Err(e) => {
+            // 选项本身无效时无法确定格式，按文本输出
+            eprintln!("Error: {}", e);
+            process::exit(e.kind.exit_code());
+        }
+    };
+
+    init_logging(&options);
+
+    if let Err(e) = try_main(&args, &options) {
+        let kind = cli_error::classify(e.as_ref());
+        match options.error_format {
+            OutputFormat::Json => eprintln!("{}", cli_error::to_json(e.as_ref())),
+            // 以可读的形式打印错误（例如逐字段列出校验错误），而不是 Debug 格式
+            OutputFormat::Text => {
+                eprintln!("Error: {}", e);
+                if kind == ErrorKind::Usage {
+                    eprintln!("Run 'help' to see all commands.");
+                }
+            }
+        }
+        process::exit(kind.exit_code());
+    }
+}
+
+/// 从参数中取出全局选项：`--dry-run`、`-v`/`-vv`/`-q`、`--error-format`、`--log-format`、`--fields`、`--lang` 和模板选项。
+///
+/// 选项只能写在命令之前：遇到第一个不是选项的参数（命令）或 `--` 后停止解析，
+/// 之后的参数原样保留，因此 `-q` 这样的标题不会被当作选项。
+fn take_options(args: &mut Vec<String>) -> Result<Options, CliError> {
+    let mut options = Options {
+        dry_run: false,
+        verbosity: 0,
+        error_format: OutputFormat::Text,
+        log_format: OutputFormat::Text,
+        template: None,
+        fields: None,
+        lang: None,
+    };
+    let mut rest = Vec::with_capacity(args.len());
+    let mut iter = args.drain(..);
+    // 第一个参数是程序名
+    rest.extend(iter.next());
+    while let Some(arg) = iter.next() {
+        if arg == "--" || !arg.starts_with('-') {
+            if arg != "--" {
+                rest.push(arg);
+            }
+            rest.extend(iter.by_ref());
+            break;
+        }
+        // 取值选项既可以写成 `--name value`，也可以写成 `--name=value`
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        let slot = match name.as_str() {
+            "--template" | "--template-file" => {
+                let value = value
+                    .or_else(|| iter.next())
+                    .ok_or_else(|| CliError::usage(format!("{} expects a value", name)))?;
+                let source = if name == "--template" {
+                    value
+                } else {
+                    fs::read_to_string(&value).map_err(|e| {
+                        CliError::new(ErrorKind::Storage, format!("cannot read template '{}': {}", value, e))
+                    })?
+                };
+                options.template = Some(MovieTemplate::new(source)?);
+                continue;
+            }
+            "--fields" => {
+                let value = value.or_else(|| iter.next()).unwrap_or_default();
+                let fields = Field::parse_list(&value)
+                    .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("--fields: {}", e)))?;
+                options.fields = Some(fields);
+                continue;
+            }
+            "--lang" => {
+                let value = value
+                    .or_else(|| iter.next())
+                    .ok_or_else(|| CliError::usage("--lang expects a language code"))?;
+                options.lang = Some(value);
+                continue;
+            }
+            "--error-format" => &mut options.error_format,
+            "--log-format" => &mut options.log_format,
+            _ => {
+                match arg.as_str() {
+                    "--dry-run" => options.dry_run = true,
+                    "-v" | "--verbose" => options.verbosity += 1,
+                    "-vv" => options.verbosity += 2,
+                    "-q" | "--quiet" => options.verbosity = -1,
+                    _ => return Err(CliError::usage(format!("unknown option '{}'", arg))),
+                }
+                continue;
+            }
+        };
+        let value = value.or_else(|| iter.next()).unwrap_or_default();
+        *slot = OutputFormat::from_str(&value).ok_or_else(|| {
+            CliError::usage(format!("{} expects 'text' or 'json', got '{}'", name, value))
+        })?;
+    }
+    drop(iter);
+    *args = rest;
+    Ok(options)
+}
+
+/// 初始化日志，写到标准错误。
+///
+/// 默认只输出警告；`-v` 输出每个操作及其耗时，`-vv` 还会输出每条 SQL 语句及其耗时，`-q` 只输出错误。
+/// `RUST_LOG` 中的过滤规则（例如 `sqlite_movies::sql=trace`）会叠加在这个默认级别之上。
+fn init_logging(options: &Options) {
+    let level = match options.verbosity {
+        i8::MIN..=-1 => LevelFilter::ERROR,
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr);
+    match options.log_format {
+        OutputFormat::Json => builder.json().init(),
+        OutputFormat::Text => builder.init(),
+    }
+}
+
+fn try_main(args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
     // 检查是否提供了命令
     if args.len() < 2 {
-        eprintln!("Error: No command provided.");
-        print_help();
-        process::exit(1);
+        return Err(CliError::usage("no command provided").into());
     }
 
     // 解析命令
     let command_str = &args[1];
     let command = match Command::from_str(command_str) {
         Some(cmd) => cmd,
-        None => {
-            eprintln!("Error: Unknown command '{}'", command_str);
-            print_help();
-            process::exit(1);
-        }
+        None => return Err(unknown_command(command_str, &Command::NAMES).into()),
     };
 
+    // 健康检查自己打开数据库，不执行迁移
+    if let Command::Doctor = command {
+        let fix = match args.get(2).map(String::as_str) {
+            None => false,
+            Some("--fix") if args.len() == 3 && !options.dry_run => true,
+            Some(_) => return Err(CliError::usage("Usage: doctor [--fix] (without --dry-run)").into()),
+        };
+        return run_doctor(fix);
+    }
+
+    if options.fields.is_some() && !matches!(command, Command::Read | Command::List | Command::Search | Command::Export) {
+        return Err(CliError::usage("--fields can only be used with read, list, search and export").into());
+    }
+    if options.lang.is_some() && !matches!(command, Command::Read | Command::List | Command::Search) {
+        return Err(CliError::usage("--lang can only be used with read, list and search").into());
+    }
+
     // 初始化电影管理器
-    let manager = MovieManager::new("movies.db")?;
+    let manager = MovieManager::new(DATABASE)?;
+
+    if let Command::Serve = command {
+        if options.dry_run {
+            return Err(CliError::usage("--dry-run cannot be used with serve").into());
+        }
+        let bind = match args.get(2).map(String::as_str) {
+            None => "127.0.0.1:8080",
+            Some("--bind") if args.len() == 4 => args[3].as_str(),
+            Some(_) => {
+                return Err(CliError::usage("Usage: serve [--bind <addr>]").into());
+            }
+        };
+        let server = MovieServer::bind(bind, manager).map_err(|e| e as Box<dyn Error>)?;
+        println!("Serving movies on http://{}", bind);
+        server.run();
+        return Ok(());
+    }
+
+    if let Command::Watch = command {
+        if options.dry_run {
+            return Err(CliError::usage("--dry-run cannot be used with watch").into());
+        }
+        return run_watch(&manager, args);
+    }
+
+    if let Command::Tui = command {
+        if options.dry_run || args.len() != 2 {
+            return Err(CliError::usage("Usage: tui (without --dry-run)").into());
+        }
+        return run_tui(&manager);
+    }
+
+    if options.dry_run {
+        let ((), changes) = manager.dry_run(|m| execute(m, command, args, options))?;
+        print_changes(&changes);
+        Ok(())
+    } else {
+        execute(&manager, command, args, options)
+    }
+}
+
+/// 启动终端界面。
+#[cfg(feature = "tui")]
+fn run_tui(manager: &MovieManager) -> Result<(), Box<dyn Error>> {
+    tui::run(manager)
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_manager: &MovieManager) -> Result<(), Box<dyn Error>> {
+    Err(CliError::usage("tui is not available: the binary was built without the `tui` feature").into())
+}
+
+/// 以 NDJSON 持续输出变更，每行一个事件，直到被中断或输出管道关闭。
+///
+/// 默认只输出启动之后的变更；`--since 0` 从最早的记录开始。
+fn run_watch(manager: &MovieManager, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "Usage: watch [--since <seq>] [--interval <ms>]";
+    let mut since = None;
+    let mut interval = Duration::from_millis(500);
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        let value = rest.next().ok_or_else(|| CliError::usage(usage))?;
+        let number: u64 = value
+            .parse()
+            .map_err(|_| CliError::invalid_argument(arg, value, "a non-negative integer", "100"))?;
+        match arg.as_str() {
+            "--since" => since = Some(number),
+            "--interval" => interval = Duration::from_millis(number),
+            _ => return Err(CliError::usage(usage).into()),
+        }
+    }
+    let since = match since {
+        Some(seq) => seq,
+        None => manager.last_change_seq()?,
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut written = Ok(());
+    manager.watch_changes(since, interval, |event| {
+        written = serde_json::to_writer(&mut stdout, &event)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(stdout))
+            .and_then(|()| stdout.flush());
+        written.is_ok()
+    })?;
+    match written {
+        // 下游（例如 `head`）关闭管道时正常退出
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        other => Ok(other?),
+    }
+}
 
+/// 检查数据库并打印报告，`fix` 时先修复。发现问题时以存储错误退出。
+fn run_doctor(fix: bool) -> Result<(), Box<dyn Error>> {
+    if fix {
+        let repaired = doctor::repair(DATABASE)?;
+        if let Some(version) = repaired.migrated_from {
+            println!("Migrated schema from version {} to {}.", version, SCHEMA_VERSION);
+        }
+        println!("Trimmed whitespace in {} rows.", repaired.trimmed);
+        println!("Removed {} aliases of missing directors.", repaired.orphan_aliases);
+        println!("Rebuilt indexes and vacuumed the database.");
+        println!();
+    }
+
+    let report = doctor::check(DATABASE)?;
+    println!(
+        "File size: {} bytes ({} pages of {} bytes, {} free)",
+        report.file_size, report.page_count, report.page_size, report.free_pages
+    );
+    let status = if report.schema_current() { "ok" } else { "run `doctor --fix` to migrate" };
+    println!("Schema version: {} (expected {}) {}", report.schema_version, SCHEMA_VERSION, status);
+    if report.integrity_errors.is_empty() {
+        println!("Integrity check: ok");
+    } else {
+        println!("Integrity check: {} problems", report.integrity_errors.len());
+        for message in &report.integrity_errors {
+            println!("    {}", message);
+        }
+    }
+    if report.foreign_key_violations.is_empty() {
+        println!("Foreign key check: ok");
+    } else {
+        println!("Foreign key check: {} violations", report.foreign_key_violations.len());
+        for violation in &report.foreign_key_violations {
+            let rowid = violation.rowid.map_or("?".to_string(), |id| id.to_string());
+            println!("    {} row {} references a missing row in {}", violation.table, rowid, violation.parent);
+        }
+    }
+    if !report.schema_current() {
+        println!("Movies: not checked until the schema is migrated");
+    } else if report.invalid_movies.is_empty() {
+        println!("Movies: ok");
+    } else {
+        println!("Movies: {} invalid", report.invalid_movies.len());
+        for (movie, error) in &report.invalid_movies {
+            for field in &error.errors {
+                println!("    ID {}: {}: {}", movie.id, field.field, field.message);
+            }
+        }
+    }
+
+    // 只有字段无效的电影时按无效输入退出，数据库本身的问题按存储错误退出
+    let kind = if report.storage_problem_count() > 0 { ErrorKind::Storage } else { ErrorKind::InvalidInput };
+    match report.problem_count() {
+        0 => Ok(()),
+        count => Err(CliError::new(kind, format!("problems found: {}", count)).into()),
+    }
+}
+
+/// 执行需要具体 SQLite 管理器的命令（事务、索引），其余命令交给 `run`。
+fn execute(manager: &MovieManager, command: Command, args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::NaturalKey => {
+            match args.get(2).map(String::as_str) {
+                None if args.len() == 2 => {}
+                Some("on") if args.len() == 3 => manager.set_natural_key_unique(true)?,
+                Some("off") if args.len() == 3 => manager.set_natural_key_unique(false)?,
+                _ => {
+                    return Err(CliError::usage("Usage: natural-key [on|off]").into());
+                }
+            }
+            let state = if manager.natural_key_unique()? { "on" } else { "off" };
+            options.report(format_args!("Natural key uniqueness is {}.", state));
+            Ok(())
+        }
+        Command::Director => run_director(manager, args, options),
+        Command::Title => run_title(manager, args, options),
+        Command::Dump => {
+            let text = manager.dump()?.to_toml();
+            match args.get(2) {
+                None => print!("{}", text),
+                // 试运行不写任何文件
+                Some(path) if args.len() == 3 && options.dry_run => {
+                    println!("Would write {} bytes to {}.", text.len(), path);
+                }
+                Some(path) if args.len() == 3 => fs::write(path, text)?,
+                Some(_) => return Err(CliError::usage("Usage: dump [file]").into()),
+            }
+            Ok(())
+        }
+        Command::Load => {
+            let text = match args.get(2).map(String::as_str) {
+                Some("-") if args.len() == 3 => io::read_to_string(io::stdin())?,
+                Some(path) if args.len() == 3 => fs::read_to_string(path)?,
+                _ => return Err(CliError::usage("Usage: load <file|->").into()),
+            };
+            let dump = Dump::from_toml(&text)
+                .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("invalid dump: {}", e)))?;
+            // 先逐条校验，以便在错误中指出是哪一部电影
+            for movie in &dump.movies {
+                movie
+                    .validate(&ValidationRules::default())
+                    .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("movie {}: {}", movie.id, e)))?;
+            }
+            manager.load(&dump)?;
+            options.report(format_args!(
+                "Loaded {} directors and {} movies.",
+                dump.directors.len(),
+                dump.movies.len()
+            ));
+            Ok(())
+        }
+        Command::Export => {
+            if args.len() != 3 {
+                return Err(CliError::usage("Usage: export <file.xlsx>").into());
+            }
+            if options.dry_run {
+                println!("Would export {} movies to {}.", manager.list_movies()?.len(), args[2]);
+                return Ok(());
+            }
+            manager.export_xlsx(&args[2], options.fields())?;
+            options.report(format_args!("Movies exported to {}.", args[2]));
+            Ok(())
+        }
+        Command::Explain => {
+            let filter = parse_filter(&args[2..])?;
+            let (sql, params, plan) = manager.explain_search(&filter)?;
+            println!("SQL: {}", sql);
+            println!("Parameters: {:?}", params);
+            println!("Query plan:");
+            for step in plan {
+                println!("    {}", step);
+            }
+            Ok(())
+        }
+        Command::Batch => {
+            if args.len() != 3 {
+                return Err(CliError::usage("Usage: batch <file>").into());
+            }
+            run_batch(manager, &args[2], options)
+        }
+        // 整个导入放在一个事务中
+        Command::Import => manager.transaction(|m| run(m, command, args, options)),
+        _ => run(manager, command, args, options),
+    }
+}
+
+/// 导演管理子命令。导演可以用规范名称或任一别名指定。
+fn run_director(manager: &MovieManager, args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
+    let words: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["list"] => {
+            for director in manager.list_directors()? {
+                let aliases = if director.aliases.is_empty() {
+                    String::new()
+                } else {
+                    format!(", Aliases: {}", director.aliases.join("; "))
+                };
+                println!(
+                    "ID: {}, Name: {}, Movies: {}{}",
+                    director.id, director.name, director.movie_count, aliases
+                );
+            }
+        }
+        ["rename", name, new_name] => {
+            manager.rename_director(name, new_name)?;
+            options.report("Director renamed.");
+        }
+        ["merge", from, into] => {
+            let moved = manager.merge_directors(from, into)?;
+            options.report(format_args!("Directors merged; {} movies moved.", moved));
+        }
+        ["alias", name, alias] => {
+            manager.add_director_alias(name, alias)?;
+            options.report("Alias added.");
+        }
+        _ => {
+            return Err(CliError::usage("Usage: director list | rename <name> <new_name> | merge <from> <into> | alias <name> <alias>").into());
+        }
+    }
+    Ok(())
+}
+
+/// 别名标题子命令。
+fn run_title(manager: &MovieManager, args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
+    let usage = "Usage: title list <id> | add <id> <lang> <original|localized|working> <title> | remove <id> <title>";
+    let words: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["list", id] => {
+            let id = parse_id(id)?;
+            if manager.read_movie(id)?.is_none() {
+                return Err(movie_not_found(manager, id).into());
+            }
+            for alternate in manager.alternate_titles(id)? {
+                println!(
+                    "Lang: {}, Kind: {}, Title: {}",
+                    alternate.lang, alternate.kind, alternate.title
+                );
+            }
+        }
+        ["add", id, lang, kind, title] => {
+            let id = parse_id(id)?;
+            let kind = TitleKind::parse(kind).ok_or_else(|| {
+                CliError::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid title kind '{}' (expected original, localized or working)", kind),
+                )
+            })?;
+            manager.add_alternate_title(id, title, lang, kind)?;
+            options.report("Alternate title added.");
+        }
+        ["remove", id, title] => {
+            let id = parse_id(id)?;
+            match manager.remove_alternate_title(id, title)? {
+                0 => {
+                    let message = format!("movie {} has no alternate title '{}'", id, title);
+                    return Err(CliError::new(ErrorKind::NotFound, message).into());
+                }
+                removed => options.report(format_args!("Removed {} alternate titles.", removed)),
+            }
+        }
+        _ => return Err(CliError::usage(usage).into()),
+    }
+    Ok(())
+}
+
+/// 在一个事务中依次执行批处理文件中的命令，任何一行出错都会回滚全部修改。
+///
+/// 每行一个命令，参数可以用单引号或双引号包裹；空行和以 `#` 开头的行会被忽略。
+fn run_batch(manager: &MovieManager, path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    manager.transaction(|m| {
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_error = |source: Box<dyn Error>| LineError { line: index + 1, source };
+
+            // 第一个元素占据程序名的位置，使参数下标与命令行一致
+            let mut words = vec![path.to_string()];
+            let split = split_words(line).map_err(|e| CliError::new(ErrorKind::InvalidInput, e));
+            words.extend(split.map_err(|e| line_error(e.into()))?);
+            let command = match Command::from_str(&words[1]) {
+                Some(cmd @ (Command::Create | Command::Update | Command::Upsert | Command::Delete)) => cmd,
+                None => {
+                    let error = unknown_command(&words[1], &["create", "update", "upsert", "delete"]);
+                    return Err(line_error(error.into()).into());
+                }
+                _ => {
+                    let message = format!("'{}' is not allowed in a batch", words[1]);
+                    return Err(line_error(CliError::usage(message).into()).into());
+                }
+            };
+            run(m, command, &words, options).map_err(line_error)?;
+        }
+        Ok(())
+    })
+}
+
+/// 按空白拆分一行，支持单引号和双引号。
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+/// 打印试运行的结果。
+///
+/// 变化来自回滚前读回的记录，显示的是归一化和解析导演别名之后实际会保存的值。
+fn print_changes(changes: &ChangeSet) {
+    println!("Dry run: no changes were written.");
+    if changes.is_empty() {
+        println!("No rows would change.");
+        return;
+    }
+    for movie in &changes.inserted {
+        print!("Would insert: ");
+        print_movie(movie);
+    }
+    for (before, after) in &changes.updated {
+        println!("Would update ID {}:", before.id);
+        let fields = [
+            ("title", &before.title, &after.title),
+            ("director", &before.director, &after.director),
+            ("release_date", &before.release_date, &after.release_date),
+        ];
+        for (field, old, new) in fields {
+            if old != new {
+                println!("    {}: {:?} -> {:?}", field, old, new);
+            }
+        }
+    }
+    for movie in &changes.deleted {
+        print!("Would delete: ");
+        print_movie(movie);
+    }
+}
+
+/// 解析过滤条件，错误视为无效输入。
+fn parse_filter<S: AsRef<str>>(terms: &[S]) -> Result<MovieFilter, CliError> {
+    MovieFilter::parse(terms).map_err(|e| CliError::new(ErrorKind::InvalidInput, e))
+}
+
+/// 按条件批量更新或删除：`--where` 与 search 使用相同的过滤语言，每个 `--where` 一个条件。
+fn run_bulk<S: MovieStore>(store: &S, command: Command, args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
+    let is_update = matches!(command, Command::Update);
+    let usage = if is_update {
+        "Usage: update --where <filter>... --set <field=value>... [--yes]"
+    } else {
+        "Usage: delete --where <filter>... [--yes]"
+    };
+
+    let mut terms = Vec::new();
+    let (mut title, mut director, mut release_date) = (None, None, None);
+    let mut yes = false;
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match (arg.as_str(), rest.clone().next()) {
+            ("--where", Some(term)) => {
+                terms.push(term.clone());
+                rest.next();
+            }
+            ("--set", Some(assignment)) if is_update => {
+                let (slot, value) = match assignment.split_once('=') {
+                    Some(("title", value)) => (&mut title, value),
+                    Some(("director", value)) => (&mut director, value),
+                    Some(("release_date", value)) => (&mut release_date, value),
+                    _ => {
+                        return Err(CliError::usage(format!(
+                            "--set expects title=, director= or release_date=, got '{}'",
+                            assignment
+                        ))
+                        .into());
+                    }
+                };
+                *slot = Some(value.to_string());
+                rest.next();
+            }
+            ("--yes", _) => yes = true,
+            _ => {
+                return Err(CliError::usage(usage).into());
+            }
+        }
+    }
+    if is_update && title.is_none() && director.is_none() && release_date.is_none() {
+        return Err(CliError::usage(usage).into());
+    }
+
+    let filter = parse_filter(&terms)?;
+    // 只有 sort= 的条件会匹配所有电影，与空的词一样拒绝
+    if !filter.has_criteria() {
+        return Err(CliError::new(ErrorKind::InvalidInput, "--where needs at least one term that matches movies (sort= alone matches every movie)").into());
+    }
+    let count = store.search_movies(&filter)?.len();
+    let verb = if is_update { "update" } else { "delete" };
+    // 试运行不会写入，无需确认
+    if count > CONFIRM_THRESHOLD && !yes && !options.dry_run {
+        print!("This will {} {} movies. Continue? [y/N] ", verb, count);
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let affected = if is_update {
+        store.update_where(&filter, title, director, release_date)?
+    } else {
+        store.delete_where(&filter)?
+    };
+    let past = if is_update { "Updated" } else { "Deleted" };
+    options.report(format_args!("{} {} movies.", past, affected));
+    Ok(())
+}
+
+/// 模糊搜索：`search --fuzzy [--top <n>] <text>`，按得分从高到低输出。
+fn run_fuzzy<S: MovieStore>(store: &S, args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
+    let usage = "Usage: search --fuzzy [--top <n>] <text>";
+    let mut top = FUZZY_TOP;
+    let mut words = Vec::new();
+    let mut rest = args[3..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--top" {
+            let value = rest.next().ok_or_else(|| CliError::usage(usage))?;
+            top = value
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| CliError::invalid_argument("--top", value, "a positive integer", "10"))?;
+        } else {
+            words.push(arg.as_str());
+        }
+    }
+    if words.is_empty() {
+        return Err(CliError::usage(usage).into());
+    }
+
+    let matches = store.fuzzy_search(&words.join(" "), top)?;
+    let records = query_records(store, options, |fields| {
+        Ok(matches.iter().map(|m| MovieRecord::from_movie(&m.movie, fields)).collect())
+    })?;
+    if options.template.is_some() {
+        return Ok(print_records(&records, options)?);
+    }
+    for (m, record) in matches.iter().zip(&records) {
+        println!("Score: {:.2}, {}", m.score, record_line(record, options));
+    }
+    Ok(())
+}
+
+/// 解析命令中的 `<id|title>`，返回电影ID；用户取消选择时返回 `None`。
+///
+/// 数字总是按ID处理。否则按标题模糊匹配：只有一部电影的标题（或别名标题）完全相同时直接使用；
+/// 否则列出候选让用户选择。`confirm` 为 false 时（只读的 read）唯一的候选也直接使用。
+fn resolve_movie<S: MovieStore>(store: &S, input: &str, confirm: bool) -> Result<Option<u32>, Box<dyn Error>> {
+    if !input.is_empty() && input.trim_start_matches('-').bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(Some(parse_id(input)?));
+    }
+    let matches = store.fuzzy_search(input, FUZZY_TOP)?;
+    let exact: Vec<&FuzzyMatch> = matches.iter().filter(|m| m.is_exact()).collect();
+    if let [only] = exact.as_slice() {
+        return Ok(Some(only.movie.id));
+    }
+    match matches.as_slice() {
+        [] => Err(CliError::new(ErrorKind::NotFound, format!("no movie matches '{}'", input)).into()),
+        [only] if !confirm => Ok(Some(only.movie.id)),
+        _ => choose_movie(input, &matches),
+    }
+}
+
+/// 列出候选电影并读取用户的选择。标准输入不是终端时无法选择，以冲突错误列出候选。
+fn choose_movie(input: &str, matches: &[FuzzyMatch]) -> Result<Option<u32>, Box<dyn Error>> {
+    let candidates: Vec<String> = matches
+        .iter()
+        .map(|m| format!("ID {}: {} ({}, score {:.2})", m.movie.id, m.movie.title, m.movie.release_date, m.score))
+        .collect();
+    if !io::stdin().is_terminal() {
+        let message = format!("'{}' matches {} movies; use an ID instead:\n  {}", input, matches.len(), candidates.join("\n  "));
+        return Err(CliError::new(ErrorKind::Conflict, message).into());
+    }
+
+    println!("Movies matching '{}':", input);
+    for (i, candidate) in candidates.iter().enumerate() {
+        println!("  [{}] {}", i + 1, candidate);
+    }
+    print!("Select a movie [1-{}] (Enter to cancel): ", matches.len());
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    match answer.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| matches.get(i)) {
+        Some(m) => Ok(Some(m.movie.id)),
+        None => Err(CliError::new(ErrorKind::InvalidInput, format!("invalid selection '{}'", answer)).into()),
+    }
+}
+
+/// 在任意存储后端上执行命令。
+fn run<S: MovieStore>(store: &S, command: Command, args: &[String], options: &Options) -> Result<(), Box<dyn Error>> {
     // 根据命令执行相应的操作
     match command {
+        Command::Update | Command::Delete if args.get(2).map(String::as_str) == Some("--where") => {
+            run_bulk(store, command, args, options)?;
+        }
         Command::Create => {
             if args.len() != 6 {
-                eprintln!("Usage: create <id> <title> <director> <release_date>");
-                process::exit(1);
+                return Err(CliError::usage("Usage: create <id> <title> <director> <release_date>").into());
             }
-            let id: u32 = args[2].parse()?;
+            let id = parse_id(&args[2])?;
             let title = args[3].clone();
             let director = args[4].clone();
             let release_date = args[5].clone();
-            manager.create_movie(id, title, director, release_date)?;
-            println!("Movie created.");
+            store.create_movie(id, title, director, release_date)?;
+            options.report("Movie created.");
         }
         Command::Read => {
             if args.len() != 3 {
-                eprintln!("Usage: read <id>");
-                process::exit(1);
+                return Err(CliError::usage("Usage: read <id|title>").into());
             }
-            let id: u32 = args[2].parse()?;
-            if let Some(movie) = manager.read_movie(id)? {
-                println!(
-                    "ID: {}, Title: {}, Director: {}, Release Date: {}",
-                    movie.id, movie.title, movie.director, movie.release_date
-                );
-            } else {
-                println!("Movie with ID {} not found.", id);
+            let Some(id) = resolve_movie(store, &args[2], false)? else {
+                println!("Aborted.");
+                return Ok(());
+            };
+            let records = query_records(store, options, |fields| {
+                store.read_fields(id, fields).map(|record| record.into_iter().collect())
+            })?;
+            if records.is_empty() {
+                return Err(movie_not_found(store, id).into());
             }
+            print_records(&records, options)?;
         }
         Command::Update => {
             if args.len() < 3 {
-                eprintln!("Usage: update <id> [title] [director] [release_date]");
-                process::exit(1);
+                return Err(CliError::usage("Usage: update <id|title> [title] [director] [release_date]").into());
             }
-            let id: u32 = args[2].parse()?;
+            let Some(id) = resolve_movie(store, &args[2], true)? else {
+                println!("Aborted.");
+                return Ok(());
+            };
             let title = args.get(3).cloned();
             let director = args.get(4).cloned();
             let release_date = args.get(5).cloned();
 
-            if manager.update_movie(id, title, director, release_date)? {
-                println!("Movie updated.");
-            } else {
-                println!("Movie with ID {} not found.", id);
+            if !store.update_movie(id, title, director, release_date)? {
+                return Err(movie_not_found(store, id).into());
+            }
+            options.report("Movie updated.");
+        }
+        Command::Upsert => {
+            if args.len() != 6 {
+                return Err(CliError::usage("Usage: upsert <id> <title> <director> <release_date>").into());
+            }
+            let id = parse_id(&args[2])?;
+            let movie = Movie::new(id, args[3].clone(), args[4].clone(), args[5].clone());
+            match store.upsert_movie(movie)? {
+                UpsertOutcome::Inserted => options.report("Movie inserted."),
+                UpsertOutcome::Updated(id) => options.report(format_args!("Movie with ID {} updated.", id)),
             }
         }
         Command::Delete => {
             if args.len() != 3 {
-                eprintln!("Usage: delete <id>");
-                process::exit(1);
+                return Err(CliError::usage("Usage: delete <id|title>").into());
             }
-            let id: u32 = args[2].parse()?;
-            if manager.delete_movie(id)? {
-                println!("Movie deleted.");
-            } else {
-                println!("Movie with ID {} not found.", id);
+            let Some(id) = resolve_movie(store, &args[2], true)? else {
+                println!("Aborted.");
+                return Ok(());
+            };
+            if !store.delete_movie(id)? {
+                return Err(movie_not_found(store, id).into());
             }
+            options.report("Movie deleted.");
         }
         Command::List => {
-            manager.list_movies()?;
+            // list 只接受排序方式，过滤请使用 search
+            let filter = parse_filter(&args[2..])?;
+            if filter.has_criteria() {
+                return Err(CliError::usage("Usage: list [sort=<id|title|director|year>]").into());
+            }
+            print_records(&query_records(store, options, |fields| store.search_fields(&filter, fields))?, options)?;
+        }
+        Command::Search if args.get(2).map(String::as_str) == Some("--fuzzy") => {
+            run_fuzzy(store, args, options)?;
+        }
+        Command::Search => {
+            let filter = parse_filter(&args[2..])?;
+            print_records(&query_records(store, options, |fields| store.search_fields(&filter, fields))?, options)?;
+        }
+        Command::Import => {
+            if args.len() != 3 {
+                return Err(CliError::usage("Usage: import <file.csv|file.xlsx>").into());
+            }
+            let path = Path::new(&args[2]);
+            let file = File::open(path)?;
+            let is_xlsx = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx"));
+            let report = if is_xlsx {
+                import_xlsx(store, file)?
+            } else {
+                import_csv(store, file)?
+            };
+            for (line, reason) in &report.failures {
+                eprintln!("Line {}: {}", line, reason.replace('\n', "\n    "));
+            }
+            options.report(format_args!(
+                "Imported {} movies, skipped {}.",
+                report.imported,
+                report.failures.len()
+            ));
+        }
+        Command::Batch | Command::Director | Command::Title | Command::Dump | Command::Export | Command::Load | Command::Explain | Command::NaturalKey | Command::Serve | Command::Tui | Command::Doctor | Command::Watch => {
+            unreachable!("handled before dispatch")
         }
         Command::Help => {
             print_help();
@@ -134,3 +1135,27 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_options_stops_at_command() {
+        let mut args: Vec<String> = ["sqlite", "-q", "--fields", "id", "create", "2", "-q", "--dry-run", "2001/1/1"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let options = take_options(&mut args).unwrap();
+        assert_eq!(options.verbosity, -1);
+        assert!(!options.dry_run);
+        assert_eq!(options.fields(), &[Field::Id]);
+        assert_eq!(args, ["sqlite", "create", "2", "-q", "--dry-run", "2001/1/1"]);
+
+        let mut args: Vec<String> = ["sqlite", "--", "-q"].iter().map(|s| s.to_string()).collect();
+        take_options(&mut args).unwrap();
+        assert_eq!(args, ["sqlite", "-q"]);
+        let mut args: Vec<String> = ["sqlite", "--bogus", "list"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(take_options(&mut args).err().map(|e| e.kind), Some(ErrorKind::Usage));
+    }
+}
@@ -0,0 +1,432 @@
+//! 线程安全的连接池。
+//!
+//! `PooledMovieManager` 可以在线程之间克隆共享：所有写操作经过唯一的写连接，
+//! 读操作使用单独的只读连接池。数据库以 WAL 模式打开，读写互不阻塞。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rusqlite::{Connection, OpenFlags};
+
+#[cfg(feature = "toml")]
+use crate::Dump;
+use crate::{
+    AlternateTitle, ChangeEvent, ChangeSet, Director, Error, Field, FuzzyMatch, Movie, MovieFilter,
+    MovieManager, MovieRecord, MovieStore, Result, TitleKind, UpsertOutcome, ValidationRules,
+};
+
+/// 连接池配置。
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// 遇到锁时等待的最长时间。
+    pub busy_timeout: Duration,
+    /// 最多保留的空闲读连接数，超出的连接用完即关闭。
+    pub max_idle_readers: usize,
+    /// 写入时使用的校验规则。
+    pub validation: ValidationRules,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            busy_timeout: Duration::from_secs(5),
+            max_idle_readers: 4,
+            validation: ValidationRules::default(),
+        }
+    }
+}
+
+struct Inner {
+    db_path: String,
+    config: PoolConfig,
+    writer: Mutex<MovieManager>,
+    readers: Mutex<Vec<MovieManager>>,
+}
+
+/// 可跨线程共享的电影管理器。
+///
+/// 除 `MovieStore` 外，还提供与 `MovieManager` 同名的别名标题、自然键、导演、`explain_search`、
+/// dump/load、变更记录、事务和试运行方法：写操作使用写连接，读操作使用读连接。
+/// `transaction` 和 `dry_run` 在持有写连接期间执行闭包，闭包中的读写都在同一个事务里。
+#[derive(Clone)]
+pub struct PooledMovieManager {
+    inner: Arc<Inner>,
+}
+
+impl PooledMovieManager {
+    /// 使用默认配置打开数据库。
+    pub fn new(db_path: &str) -> Result<Self> {
+        Self::with_config(db_path, PoolConfig::default())
+    }
+
+    /// 使用指定配置打开数据库，并切换到 WAL 模式。
+    ///
+    /// 读连接需要独立打开同一个文件，因此不支持 `:memory:` 数据库。
+    pub fn with_config(db_path: &str, config: PoolConfig) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(config.busy_timeout)?;
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        let writer = MovieManager::from_connection(conn)?.with_rules(config.validation.clone());
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                db_path: db_path.to_string(),
+                config,
+                writer: Mutex::new(writer),
+                readers: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    /// 为电影添加一个别名标题。
+    pub fn add_alternate_title(
+        &self,
+        movie_id: u32,
+        title: &str,
+        lang: &str,
+        kind: TitleKind,
+    ) -> Result<()> {
+        self.writer()
+            .add_alternate_title(movie_id, title, lang, kind)
+    }
+
+    /// 删除电影中与 `title` 相同的别名标题，返回删除的数量。
+    pub fn remove_alternate_title(&self, movie_id: u32, title: &str) -> Result<usize> {
+        self.writer().remove_alternate_title(movie_id, title)
+    }
+
+    /// 列出一部电影的别名标题。
+    pub fn alternate_titles(&self, movie_id: u32) -> Result<Vec<AlternateTitle>> {
+        self.with_reader(|reader| reader.alternate_titles(movie_id))
+    }
+
+    /// 列出所有电影的别名标题。
+    pub fn all_alternate_titles(&self) -> Result<Vec<AlternateTitle>> {
+        self.with_reader(|reader| reader.all_alternate_titles())
+    }
+
+    /// 开启或关闭自然键唯一约束。
+    pub fn set_natural_key_unique(&self, enabled: bool) -> Result<()> {
+        self.writer().set_natural_key_unique(enabled)
+    }
+
+    /// 自然键唯一约束是否开启。
+    pub fn natural_key_unique(&self) -> Result<bool> {
+        self.with_reader(|reader| reader.natural_key_unique())
+    }
+
+    /// 列出所有导演及其别名。
+    pub fn list_directors(&self) -> Result<Vec<Director>> {
+        self.with_reader(|reader| reader.list_directors())
+    }
+
+    /// 为导演添加一个别名。
+    pub fn add_director_alias(&self, name: &str, alias: &str) -> Result<()> {
+        self.writer().add_director_alias(name, alias)
+    }
+
+    /// 修改导演的规范名称，旧名称保留为别名。
+    pub fn rename_director(&self, name: &str, new_name: &str) -> Result<()> {
+        self.writer().rename_director(name, new_name)
+    }
+
+    /// 把一位导演合并到另一位，返回转移的电影数量。
+    pub fn merge_directors(&self, from: &str, into: &str) -> Result<usize> {
+        self.writer().merge_directors(from, into)
+    }
+
+    /// 返回搜索使用的 SQL、参数和查询计划。
+    pub fn explain_search(
+        &self,
+        filter: &MovieFilter,
+    ) -> Result<(String, Vec<String>, Vec<String>)> {
+        self.with_reader(|reader| reader.explain_search(filter))
+    }
+
+    /// 导出整个目录。
+    #[cfg(feature = "toml")]
+    pub fn dump(&self) -> Result<Dump> {
+        self.with_reader(|reader| reader.dump())
+    }
+
+    /// 用导出的内容替换整个目录。
+    #[cfg(feature = "toml")]
+    pub fn load(&self, dump: &Dump) -> Result<()> {
+        self.writer().load(dump)
+    }
+
+    /// 仍保留的最早一条变更的序号。
+    pub fn first_change_seq(&self) -> Result<u64> {
+        self.with_reader(|reader| reader.first_change_seq())
+    }
+
+    /// 最新一条变更的序号。
+    pub fn last_change_seq(&self) -> Result<u64> {
+        self.with_reader(|reader| reader.last_change_seq())
+    }
+
+    /// 读取序号大于 `seq` 的所有变更。
+    pub fn changes_since(&self, seq: u64) -> Result<Vec<ChangeEvent>> {
+        self.with_reader(|reader| reader.changes_since(seq))
+    }
+
+    /// 持续监听变更，直到 `on_event` 返回 `false`。监听期间占用一个读连接。
+    pub fn watch_changes(
+        &self,
+        since: u64,
+        interval: Duration,
+        on_event: impl FnMut(ChangeEvent) -> bool,
+    ) -> Result<()> {
+        self.with_reader(|reader| reader.watch_changes(since, interval, on_event))
+    }
+
+    /// 在写连接上的事务中执行操作：成功时提交，出错时回滚。
+    pub fn transaction<T, E: From<Error>>(
+        &self,
+        f: impl FnOnce(&MovieManager) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        self.writer().transaction(f)
+    }
+
+    /// 在写连接上试运行操作并记录数据变化，然后总是回滚。
+    pub fn dry_run<T, E: From<Error>>(
+        &self,
+        f: impl FnOnce(&MovieManager) -> std::result::Result<T, E>,
+    ) -> std::result::Result<(T, ChangeSet), E> {
+        self.writer().dry_run(f)
+    }
+
+    /// 获取写连接。
+    fn writer(&self) -> MutexGuard<'_, MovieManager> {
+        self.inner.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 借出一个读连接执行操作，完成后归还到池中。
+    fn with_reader<T>(&self, f: impl FnOnce(&MovieManager) -> Result<T>) -> Result<T> {
+        let idle = self
+            .inner
+            .readers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop();
+        let reader = match idle {
+            Some(reader) => reader,
+            None => self.open_reader()?,
+        };
+
+        let result = f(&reader);
+
+        let mut readers = self.inner.readers.lock().unwrap_or_else(|e| e.into_inner());
+        if readers.len() < self.inner.config.max_idle_readers {
+            readers.push(reader);
+        }
+        result
+    }
+
+    /// 打开一个新的只读连接。
+    fn open_reader(&self) -> Result<MovieManager> {
+        let conn = Connection::open_with_flags(
+            &self.inner.db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+        conn.busy_timeout(self.inner.config.busy_timeout)?;
+        MovieManager::read_only(conn)
+    }
+}
+
+impl MovieStore for PooledMovieManager {
+    type Error = crate::Error;
+
+    fn create_movie(
+        &self,
+        id: u32,
+        title: String,
+        director: String,
+        release_date: String,
+    ) -> Result<()> {
+        self.writer()
+            .create_movie(id, title, director, release_date)
+    }
+
+    fn read_movie(&self, id: u32) -> Result<Option<Movie>> {
+        self.with_reader(|reader| reader.read_movie(id))
+    }
+
+    fn update_movie(
+        &self,
+        id: u32,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<bool> {
+        self.writer()
+            .update_movie(id, title, director, release_date)
+    }
+
+    fn delete_movie(&self, id: u32) -> Result<bool> {
+        self.writer().delete_movie(id)
+    }
+
+    fn list_movies(&self) -> Result<Vec<Movie>> {
+        self.with_reader(|reader| reader.list_movies())
+    }
+
+    fn search_movies(&self, filter: &MovieFilter) -> Result<Vec<Movie>> {
+        self.with_reader(|reader| reader.search_movies(filter))
+    }
+
+    fn read_fields(&self, id: u32, fields: &[Field]) -> Result<Option<MovieRecord>> {
+        self.with_reader(|reader| reader.read_fields(id, fields))
+    }
+
+    fn search_fields(&self, filter: &MovieFilter, fields: &[Field]) -> Result<Vec<MovieRecord>> {
+        self.with_reader(|reader| reader.search_fields(filter, fields))
+    }
+
+    fn fuzzy_search(&self, query: &str, limit: usize) -> Result<Vec<FuzzyMatch>> {
+        self.with_reader(|reader| reader.fuzzy_search(query, limit))
+    }
+
+    fn localized_titles(&self, lang: &str) -> Result<HashMap<u32, String>> {
+        self.with_reader(|reader| reader.localized_titles(lang))
+    }
+
+    fn nearest_movies(&self, id: u32, limit: usize) -> Result<Vec<Movie>> {
+        self.with_reader(|reader| reader.nearest_movies(id, limit))
+    }
+
+    fn upsert_movie(&self, movie: Movie) -> Result<UpsertOutcome> {
+        self.writer().upsert_movie(movie)
+    }
+
+    fn update_where(
+        &self,
+        filter: &MovieFilter,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<usize> {
+        self.writer()
+            .update_where(filter, title, director, release_date)
+    }
+
+    fn delete_where(&self, filter: &MovieFilter) -> Result<usize> {
+        self.writer().delete_where(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+
+    #[test]
+    fn test_pool_shared_across_threads() {
+        assert_send_sync::<PooledMovieManager>();
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let pool = PooledMovieManager::new(db.to_str().unwrap()).unwrap();
+
+        let handles: Vec<_> = (0..4u32)
+            .map(|t| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    for i in 0..25 {
+                        let id = t * 100 + i;
+                        pool.create_movie(
+                            id,
+                            format!("Movie {}", id),
+                            "Director".into(),
+                            "2000/1/1".into(),
+                        )
+                        .unwrap();
+                        assert!(pool.read_movie(id).unwrap().is_some());
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(pool.list_movies().unwrap().len(), 100);
+
+        // 别名标题和模糊搜索也走读连接
+        pool.add_alternate_title(5, "映画 5", "ja", TitleKind::Localized)
+            .unwrap();
+        assert_eq!(pool.localized_titles("ja").unwrap()[&5], "映画 5");
+        assert_eq!(pool.fuzzy_search("映画", 1).unwrap()[0].movie.id, 5);
+        // WAL 模式会持久化到数据库文件中
+        let mode: String = Connection::open(&db)
+            .unwrap()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_pool_forwards_manager_api() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let pool = PooledMovieManager::new(db.to_str().unwrap()).unwrap();
+        let create = |id: u32, title: &str, director: &str| {
+            pool.create_movie(id, title.into(), director.into(), "2010/7/16".into())
+        };
+
+        pool.set_natural_key_unique(true).unwrap();
+        assert!(pool.natural_key_unique().unwrap());
+        create(1, "Inception", "Christopher Nolan").unwrap();
+        assert!(create(2, "INCEPTION", "Christopher Nolan").is_err());
+
+        create(2, "Tenet", "C. Nolan").unwrap();
+        pool.merge_directors("C. Nolan", "Christopher Nolan")
+            .unwrap();
+        pool.add_director_alias("Christopher Nolan", "Nolan")
+            .unwrap();
+        pool.rename_director("Nolan", "Chris Nolan").unwrap();
+        let directors = pool.list_directors().unwrap();
+        assert_eq!(directors.len(), 1);
+        assert_eq!(directors[0].name, "Chris Nolan");
+        assert_eq!(directors[0].movie_count, 2);
+
+        let filter = MovieFilter::parse(&["year=2010"]).unwrap();
+        let plan = pool.explain_search(&filter).unwrap().2.join("\n");
+        assert!(plan.contains("movies_release_year"));
+
+        // 事务出错时回滚，试运行总是回滚
+        let failed: Result<()> = pool.transaction(|m| {
+            m.delete_movie(1)?;
+            Err(Error::Conflict("stop".into()))
+        });
+        assert!(failed.is_err());
+        let ((), changes) = pool.dry_run(|m| m.delete_movie(2).map(|_| ())).unwrap();
+        assert_eq!(changes.deleted.len(), 1);
+        assert_eq!(pool.list_movies().unwrap().len(), 2);
+
+        let last = pool.last_change_seq().unwrap();
+        assert_eq!(pool.first_change_seq().unwrap(), 1);
+        assert_eq!(pool.changes_since(0).unwrap().len() as u64, last);
+        pool.delete_movie(2).unwrap();
+        let mut events = Vec::new();
+        pool.watch_changes(last, Duration::from_millis(10), |event| {
+            events.push(event);
+            false
+        })
+        .unwrap();
+        assert_eq!(events[0].movie.title, "Tenet");
+
+        #[cfg(feature = "toml")]
+        {
+            let dump = pool.dump().unwrap();
+            pool.delete_movie(1).unwrap();
+            pool.load(&dump).unwrap();
+            assert_eq!(pool.list_movies().unwrap().len(), 1);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! 罗马字搜索键（拼音和日语罗马字）。
+//!
+//! 每部电影的标题和每位导演的名称都保存两个派生列：完整的罗马字（`阿凡达` → `afanda`）
+//! 和每个字的首字母（`afd`）。汉字转换为拼音（多音字取最常用的读音），假名转换为罗马字，
+//! 字母和数字去掉重音并转为小写。结果只包含 ASCII 小写字母和数字，
+//! 其他文字（西里尔字母、韩文等）没有对应的转写，直接忽略。
+//!
+//! 空白和标点分隔单词，`roman=` 从任一单词的开头匹配（`roman=knight` 能找到 `The Dark Knight`）。
+//! 派生列保存从每个单词开始的后缀，以空格分隔（`thedarkknight darkknight knight`），
+//! 因此前缀也可以跨越后面的单词（`roman=darkkn`）。连续的汉字或假名之间没有空格，算作一个单词。
+//!
+//! 限制：没有日语读音词典，日语罗马字只覆盖假名。含有假名的文本中，汉字不转换，只当作单词的分隔
+//! （`魔女の宅急便` → `no`）；不含假名的日文汉字与中文无法区分，仍按普通话拼音转换
+//! （`宮崎` → `gongqi`，而不是 `miyazaki`）。按日语读音搜索时请为电影添加假名的别名标题。
+//!
+//! 派生列在写入时由 SQL 函数 `romanize` 和 `romanize_initials` 计算。
+//! 按单词开头匹配无法使用索引，搜索时需要扫描这些列。
+
+use pinyin::ToPinyin;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use wana_kana::{ConvertJapanese, IsJapaneseChar};
+
+use crate::collation::fold;
+use crate::Result;
+
+/// 小写假名和长音符只修饰前一个音节，不产生首字母。
+const MODIFIER_KANA: &str = "ぁぃぅぇぉゃゅょゎっァィゥェォャュョヮッー";
+
+/// 整段文本完整的罗马字和首字母，单词之间不加分隔。
+pub fn romanize(text: &str) -> (String, String) {
+    words(text).into_iter().fold(
+        (String::new(), String::new()),
+        |(full, initials), (f, i)| (full + &f, initials + &i),
+    )
+}
+
+/// 派生列中保存的搜索键：从每个单词开始的后缀，以空格分隔。
+pub fn romanize_words(text: &str) -> (String, String) {
+    let words = words(text);
+    let suffixes = |part: fn(&(String, String)) -> &str| {
+        (0..words.len())
+            .map(|start| words[start..].iter().map(part).collect::<String>())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    (suffixes(|w| &w.0), suffixes(|w| &w.1))
+}
+
+/// `romanize_words` 生成的搜索键中是否有单词以 `prefix` 开头。
+pub(crate) fn has_word_prefix(keys: &str, prefix: &str) -> bool {
+    format!(" {}", keys).contains(&format!(" {}", prefix))
+}
+
+/// 把文本拆分为单词，每个单词是完整的罗马字和首字母。转换后为空的单词被丢弃。
+fn words(text: &str) -> Vec<(String, String)> {
+    // 含有假名的文本视为日文，其中的汉字没有可用的读音
+    let japanese = text.chars().any(|c| c.is_kana());
+    let mut words = Vec::new();
+    let mut full = String::new();
+    let mut initials = String::new();
+    let mut kana = String::new();
+
+    let mut end_word = |full: &mut String, initials: &mut String| {
+        if !full.is_empty() {
+            words.push((std::mem::take(full), std::mem::take(initials)));
+        }
+    };
+    for c in text.chars() {
+        if c.is_kana() {
+            kana.push(c);
+            continue;
+        }
+        flush_kana(&mut kana, &mut full, &mut initials);
+        if let Some(pinyin) = c.to_pinyin() {
+            if japanese {
+                end_word(&mut full, &mut initials);
+            } else {
+                full.push_str(pinyin.plain());
+                initials.push_str(pinyin.first_letter());
+            }
+        } else if c.is_alphanumeric() {
+            let folded = fold(c.encode_utf8(&mut [0; 4]));
+            for c in folded.chars().filter(char::is_ascii_alphanumeric) {
+                full.push(c);
+                initials.push(c);
+            }
+        } else {
+            end_word(&mut full, &mut initials);
+        }
+    }
+    flush_kana(&mut kana, &mut full, &mut initials);
+    end_word(&mut full, &mut initials);
+    words
+}
+
+/// 转换一段连续的假名。整段一起转换，拗音和促音（`きょう`、`っ`）才能正确拼写。
+fn flush_kana(kana: &mut String, full: &mut String, initials: &mut String) {
+    if kana.is_empty() {
+        return;
+    }
+    full.extend(
+        kana.as_str()
+            .to_romaji()
+            .chars()
+            .filter(char::is_ascii_alphanumeric),
+    );
+    for c in kana.chars().filter(|c| !MODIFIER_KANA.contains(*c)) {
+        let romaji = c.encode_utf8(&mut [0; 4]).to_romaji();
+        initials.extend(romaji.chars().find(char::is_ascii_alphabetic));
+    }
+    kana.clear();
+}
+
+/// 在连接上注册 `romanize(text)` 和 `romanize_initials(text)` 函数，返回 `romanize_words` 的两个搜索键。
+pub(crate) fn register(conn: &Connection) -> Result<()> {
+    let flags = || FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("romanize", 1, flags(), |ctx| {
+        Ok(ctx
+            .get::<Option<String>>(0)?
+            .map(|text| romanize_words(&text).0))
+    })?;
+    conn.create_scalar_function("romanize_initials", 1, flags(), |ctx| {
+        Ok(ctx
+            .get::<Option<String>>(0)?
+            .map(|text| romanize_words(&text).1))
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MovieFilter, MovieManager};
+
+    #[test]
+    fn test_romanized_search() {
+        assert_eq!(romanize("阿凡达"), ("afanda".into(), "afd".into()));
+        assert_eq!(
+            romanize("となりのトトロ"),
+            ("tonarinototoro".into(), "tnrnttr".into())
+        );
+        assert_eq!(romanize("ちょっと"), ("chotto".into(), "ct".into()));
+        assert_eq!(romanize("Amélie 2"), ("amelie2".into(), "amelie2".into()));
+        // 只输出 ASCII：没有转写的文字被忽略，不含假名的日文汉字按拼音转换
+        assert_eq!(romanize("Брат 2"), ("2".into(), "2".into()));
+        assert_eq!(romanize("올드보이"), (String::new(), String::new()));
+        assert_eq!(romanize("宮崎"), ("gongqi".into(), "gq".into()));
+        // 含有假名时汉字不转换，只分隔单词
+        assert_eq!(romanize("魔女の宅急便"), ("no".into(), "n".into()));
+        assert_eq!(
+            romanize_words("The Dark Knight").0,
+            "thedarkknight darkknight knight"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MovieManager::new(dir.path().join("movies.db").to_str().unwrap()).unwrap();
+        manager
+            .create_movie(1, "霸王别姬".into(), "陈凯歌".into(), "1993/1/1".into())
+            .unwrap();
+        manager
+            .create_movie(
+                2,
+                "となりのトトロ".into(),
+                "宮崎駿".into(),
+                "1988/4/16".into(),
+            )
+            .unwrap();
+        manager
+            .create_movie(
+                3,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        manager
+            .create_movie(
+                4,
+                "The Dark Knight".into(),
+                "Christopher Nolan".into(),
+                "2008/7/18".into(),
+            )
+            .unwrap();
+        let ids = |query: &str| -> Vec<u32> {
+            let filter = MovieFilter::parse(&[format!("roman={}", query)]).unwrap();
+            manager
+                .search_movies(&filter)
+                .unwrap()
+                .iter()
+                .map(|m| m.id)
+                .collect()
+        };
+        assert_eq!(ids("bawang"), vec![1]);
+        assert_eq!(ids("bwbj"), vec![1]);
+        assert_eq!(ids("chenkai"), vec![1]);
+        assert_eq!(ids("tonari"), vec![2]);
+        assert_eq!(ids("incep"), vec![3]);
+        // 从任一单词的开头匹配，前缀可以跨越后面的单词，但不匹配单词中间
+        assert_eq!(ids("knight"), vec![4]);
+        assert_eq!(ids("darkkn"), vec![4]);
+        assert_eq!(ids("nolan"), vec![3, 4]);
+        assert!(ids("night").is_empty());
+        // 中文输入同样先转换为拼音，因此也能找到同音的标题
+        assert_eq!(ids("八王"), vec![1]);
+        assert!(ids("zzz").is_empty());
+        // 转换后为空的前缀会匹配所有电影，解析时即被拒绝
+        assert!(MovieFilter::parse(&["roman=!!!"]).is_err());
+        assert!(MovieFilter::parse(&["roman=Брат"]).is_err());
+
+        // 更新标题后派生列随之更新
+        manager
+            .update_movie(1, Some("活着".into()), None, None)
+            .unwrap();
+        assert_eq!(ids("huozhe"), vec![1]);
+        assert!(ids("bawang").is_empty());
+        // 导演改名同样更新派生列
+        manager.rename_director("陈凯歌", "张艺谋").unwrap();
+        assert_eq!(ids("zym"), vec![1]);
+
+        // 按单词开头匹配需要扫描派生列
+        let filter = MovieFilter::parse(&["roman=huo"]).unwrap();
+        let plan = manager.explain_search(&filter).unwrap().2.join("\n");
+        assert!(plan.contains("SCAN"));
+    }
+}
@@ -0,0 +1,246 @@
+//! 数据库结构与迁移。
+//!
+//! 结构版本保存在 `PRAGMA user_version` 中。打开数据库时会依次执行尚未应用的迁移，
+//! 每个迁移在自己的事务中完成。
+
+use rusqlite::Connection;
+use tracing::info;
+
+use crate::Result;
+
+/// 最初的 movies 表，版本 0 的数据库只有这一张表。
+const BASE_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS movies (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        director TEXT NOT NULL,
+        release_date TEXT NOT NULL
+    )";
+
+/// 按顺序排列的迁移，第 N 个迁移把数据库从版本 N 升级到 N + 1。
+const MIGRATIONS: &[&str] = &[
+    // 1: 导演拆分到 directors 表，支持别名；movies 通过外键引用导演
+    "
+    CREATE TABLE directors (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+    CREATE TABLE director_aliases (
+        alias TEXT PRIMARY KEY COLLATE NOCASE,
+        director_id INTEGER NOT NULL REFERENCES directors(id) ON DELETE CASCADE
+    );
+    INSERT OR IGNORE INTO directors (name) SELECT DISTINCT trim(director) FROM movies;
+
+    CREATE TABLE movies_new (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        director_id INTEGER NOT NULL REFERENCES directors(id),
+        release_date TEXT NOT NULL
+    );
+    INSERT INTO movies_new (id, title, director_id, release_date)
+        SELECT m.id, m.title, d.id, m.release_date
+        FROM movies m JOIN directors d ON d.name = trim(m.director);
+    DROP TABLE movies;
+    ALTER TABLE movies_new RENAME TO movies;
+
+    CREATE VIEW movie_details AS
+        SELECT m.id, m.title, d.name AS director, m.release_date, m.director_id
+        FROM movies m JOIN directors d ON d.id = m.director_id;
+    ",
+    // 2: 按导演、年份和自然键标题查询的索引。标题和导演的子串搜索（LIKE '%..%'）无法使用索引
+    "
+    CREATE INDEX movies_director_id ON movies (director_id);
+    CREATE INDEX movies_release_year ON movies (substr(trim(release_date), 1, 4));
+    CREATE INDEX movies_title_key ON movies (lower(trim(title)));
+    ",
+    // 3: 变更记录。由触发器写入，因此其他进程的修改也会被记录
+    "
+    CREATE TABLE movie_changes (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        op TEXT NOT NULL,
+        movie_id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        director TEXT NOT NULL,
+        release_date TEXT NOT NULL,
+        changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE TRIGGER movies_after_insert AFTER INSERT ON movies BEGIN
+        INSERT INTO movie_changes (op, movie_id, title, director, release_date)
+        SELECT 'insert', NEW.id, NEW.title, name, NEW.release_date
+        FROM directors WHERE id = NEW.director_id;
+    END;
+    CREATE TRIGGER movies_after_update AFTER UPDATE ON movies
+    WHEN OLD.title IS NOT NEW.title
+      OR OLD.director_id IS NOT NEW.director_id
+      OR OLD.release_date IS NOT NEW.release_date
+    BEGIN
+        INSERT INTO movie_changes (op, movie_id, title, director, release_date)
+        SELECT 'update', NEW.id, NEW.title, name, NEW.release_date
+        FROM directors WHERE id = NEW.director_id;
+    END;
+    CREATE TRIGGER movies_after_delete AFTER DELETE ON movies BEGIN
+        INSERT INTO movie_changes (op, movie_id, title, director, release_date)
+        SELECT 'delete', OLD.id, OLD.title, coalesce((SELECT name FROM directors WHERE id = OLD.director_id), ''), OLD.release_date;
+    END;
+    -- 导演改名时，该导演的每部电影都算作一次更新
+    CREATE TRIGGER directors_after_rename AFTER UPDATE OF name ON directors
+    WHEN OLD.name IS NOT NEW.name COLLATE BINARY
+    BEGIN
+        INSERT INTO movie_changes (op, movie_id, title, director, release_date)
+        SELECT 'update', id, title, NEW.name, release_date
+        FROM movies WHERE director_id = NEW.id;
+    END;
+    ",
+    // 4: 罗马字搜索键（拼音或日语罗马字），由写入语句通过 romanize 函数维护，见 romanize 模块
+    "
+    ALTER TABLE movies ADD COLUMN title_roman TEXT NOT NULL DEFAULT '';
+    ALTER TABLE movies ADD COLUMN title_initials TEXT NOT NULL DEFAULT '';
+    ALTER TABLE directors ADD COLUMN name_roman TEXT NOT NULL DEFAULT '';
+    ALTER TABLE directors ADD COLUMN name_initials TEXT NOT NULL DEFAULT '';
+    UPDATE movies SET title_roman = romanize(title), title_initials = romanize_initials(title);
+    UPDATE directors SET name_roman = romanize(name), name_initials = romanize_initials(name);
+    CREATE INDEX movies_title_roman ON movies (title_roman);
+    CREATE INDEX movies_title_initials ON movies (title_initials);
+    CREATE INDEX directors_name_roman ON directors (name_roman);
+    CREATE INDEX directors_name_initials ON directors (name_initials);
+    ",
+    // 5: 别名标题（不同语言或地区的标题），见 titles 模块
+    "
+    CREATE TABLE alternate_titles (
+        id INTEGER PRIMARY KEY,
+        movie_id INTEGER NOT NULL REFERENCES movies(id) ON DELETE CASCADE,
+        title TEXT NOT NULL,
+        lang TEXT NOT NULL COLLATE NOCASE,
+        kind TEXT NOT NULL CHECK (kind IN ('original', 'localized', 'working')),
+        title_roman TEXT NOT NULL DEFAULT '',
+        title_initials TEXT NOT NULL DEFAULT '',
+        UNIQUE (movie_id, lang, kind, title)
+    );
+    CREATE INDEX alternate_titles_title_roman ON alternate_titles (title_roman);
+    CREATE INDEX alternate_titles_title_initials ON alternate_titles (title_initials);
+    ",
+    // 6: 自然键中的标题改为折叠大小写和重音后的 title_key，由写入语句维护。
+    // lower() 只折叠 ASCII，非 ASCII 标题与自身比较也不相等。自然键唯一索引由 migrate 按新定义重建
+    "
+    ALTER TABLE movies ADD COLUMN title_key TEXT NOT NULL DEFAULT '';
+    UPDATE movies SET title_key = fold(trim(title));
+    DROP INDEX movies_title_key;
+    CREATE INDEX movies_title_key ON movies (title_key);
+    DROP INDEX IF EXISTS movies_natural_key;
+    ",
+    // 7: romanize 只输出 ASCII，按新规则重新计算已有的罗马字派生列
+    "
+    UPDATE movies SET title_roman = romanize(title), title_initials = romanize_initials(title);
+    UPDATE directors SET name_roman = romanize(name), name_initials = romanize_initials(name);
+    UPDATE alternate_titles SET title_roman = romanize(title), title_initials = romanize_initials(title);
+    ",
+    // 8: 导演名称和别名的折叠键，解析导演时不区分大小写和重音（NOCASE 只折叠 ASCII），
+    // 由写入语句维护。已有的重复导演不会自动合并，可以用 `directors merge` 合并
+    "
+    ALTER TABLE directors ADD COLUMN name_key TEXT NOT NULL DEFAULT '';
+    ALTER TABLE director_aliases ADD COLUMN alias_key TEXT NOT NULL DEFAULT '';
+    UPDATE directors SET name_key = fold(trim(name));
+    UPDATE director_aliases SET alias_key = fold(trim(alias));
+    CREATE INDEX directors_name_key ON directors (name_key);
+    CREATE INDEX director_aliases_alias_key ON director_aliases (alias_key);
+    ",
+    // 9: 变更记录只保留最新的 10000 条（changes::CHANGE_LOG_LIMIT）。seq 单调递增，
+    // 每次插入最多删除一条最旧的记录，走主键，开销固定
+    "
+    DELETE FROM movie_changes WHERE seq <= (SELECT max(seq) FROM movie_changes) - 10000;
+    CREATE TRIGGER movie_changes_prune AFTER INSERT ON movie_changes BEGIN
+        DELETE FROM movie_changes WHERE seq <= NEW.seq - 10000;
+    END;
+    ",
+    // 10: 罗马字派生列改为以空格分隔的单词后缀，`roman=` 从任一单词的开头匹配，
+    // 前缀索引不再用得上，一并删除
+    "
+    DROP INDEX movies_title_roman;
+    DROP INDEX movies_title_initials;
+    DROP INDEX directors_name_roman;
+    DROP INDEX directors_name_initials;
+    DROP INDEX alternate_titles_title_roman;
+    DROP INDEX alternate_titles_title_initials;
+    UPDATE movies SET title_roman = romanize(title), title_initials = romanize_initials(title);
+    UPDATE directors SET name_roman = romanize(name), name_initials = romanize_initials(name);
+    UPDATE alternate_titles SET title_roman = romanize(title), title_initials = romanize_initials(title);
+    ",
+];
+
+/// 当前代码期望的结构版本。
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// 读取数据库的结构版本。
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// 创建基础表并执行所有尚未应用的迁移。
+pub(crate) fn migrate(conn: &Connection) -> Result<()> {
+    conn.execute_batch(BASE_SCHEMA)?;
+
+    let current = schema_version(conn)?;
+    if current as usize >= MIGRATIONS.len() {
+        return Ok(());
+    }
+    // 迁移可能重建 movies 表或删除自然键唯一索引，全部迁移完成后按当前的定义重新创建
+    let natural_key_unique: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'index' AND name = ?1",
+        [crate::NATURAL_KEY_INDEX],
+        |row| row.get(0),
+    )?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        info!(version = version + 1, "applying schema migration");
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version as u32 + 1)?;
+        tx.commit()?;
+    }
+    if natural_key_unique {
+        conn.execute_batch(&crate::natural_key_index_sql())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MovieManager;
+
+    #[test]
+    fn test_migrates_legacy_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        {
+            let conn = Connection::open(&db).unwrap();
+            conn.execute_batch(BASE_SCHEMA).unwrap();
+            conn.execute_batch(
+                "INSERT INTO movies VALUES (1, 'Inception', 'Christopher Nolan', '2010/7/16');
+                 INSERT INTO movies VALUES (2, 'Interstellar', ' Christopher Nolan ', '2014/11/5');",
+            )
+            .unwrap();
+        }
+
+        let manager = MovieManager::new(db.to_str().unwrap()).unwrap();
+        let directors = manager.list_directors().unwrap();
+        assert_eq!(directors.len(), 1);
+        assert_eq!(directors[0].movie_count, 2);
+        assert_eq!(
+            manager.read_movie(2).unwrap().unwrap().director,
+            "Christopher Nolan"
+        );
+        let conn = Connection::open(&db).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_filters_use_indexes() {
+        let manager = MovieManager::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let plan = |terms: &[&str]| {
+            let filter = crate::MovieFilter::parse(terms).unwrap();
+            manager.explain_search(&filter).unwrap().2.join("\n")
+        };
+        assert!(plan(&["year=2010"]).contains("movies_release_year"));
+        assert!(plan(&["director=nolan"]).contains("movies_director_id"));
+    }
+}
@@ -0,0 +1,345 @@
+//! 本地 HTTP REST 服务，将 `MovieManager` 以 JSON 接口的形式暴露出来。
+//!
+//! 路由：
+//! - `GET /movies`、`POST /movies`
+//! - `GET /movies/{id}`、`PATCH /movies/{id}`、`DELETE /movies/{id}`
+
+use std::net::SocketAddr;
+
+use rusqlite::ErrorCode;
+use serde::Deserialize;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{Error, Movie, MovieManager, NATURAL_KEY_INDEX};
+
+/// PATCH 请求体，所有字段都是可选的。
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MoviePatch {
+    pub title: Option<String>,
+    pub director: Option<String>,
+    pub release_date: Option<String>,
+}
+
+/// 一次请求处理的结果：状态码和可选的 JSON 响应体。
+struct Reply {
+    status: u16,
+    body: Option<serde_json::Value>,
+}
+
+impl Reply {
+    fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            body: Some(body),
+        }
+    }
+
+    fn empty(status: u16) -> Self {
+        Self { status, body: None }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, json!({ "error": message.into() }))
+    }
+}
+
+/// 电影 REST 服务。
+pub struct MovieServer {
+    server: Server,
+    manager: MovieManager,
+}
+
+impl MovieServer {
+    /// 在指定地址上监听，例如 `127.0.0.1:8080`。端口为 0 时由系统分配。
+    pub fn bind(
+        addr: &str,
+        manager: MovieManager,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let server = Server::http(addr)?;
+        Ok(Self { server, manager })
+    }
+
+    /// 返回实际监听的地址。
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// 依次处理到来的请求，直到服务被关闭。
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            self.handle(request);
+        }
+    }
+
+    /// 处理单个请求并写回响应。
+    fn handle(&self, mut request: Request) {
+        let mut body = String::new();
+        let reply = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => self.route(request.method(), request.url(), &body),
+            Err(_) => Reply::error(400, "request body must be valid UTF-8"),
+        };
+
+        let result = match reply.body {
+            Some(value) => {
+                let content_type =
+                    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                        .expect("static header is valid");
+                request.respond(
+                    Response::from_string(value.to_string())
+                        .with_status_code(reply.status)
+                        .with_header(content_type),
+                )
+            }
+            None => request.respond(Response::empty(reply.status)),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: failed to send response: {}", e);
+        }
+    }
+
+    /// 根据方法和路径分发请求。
+    fn route(&self, method: &Method, url: &str, body: &str) -> Reply {
+        let path = url.split('?').next().unwrap_or("");
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match segments.as_slice() {
+            ["movies"] => match method {
+                Method::Get => self.list(),
+                Method::Post => self.create(body),
+                _ => Reply::error(405, "method not allowed"),
+            },
+            ["movies", id] => {
+                let id: u32 = match id.parse() {
+                    Ok(id) => id,
+                    Err(_) => return Reply::error(400, format!("invalid movie id '{}'", id)),
+                };
+                match method {
+                    Method::Get => self.read(id),
+                    Method::Patch => self.update(id, body),
+                    Method::Delete => self.delete(id),
+                    _ => Reply::error(405, "method not allowed"),
+                }
+            }
+            _ => Reply::error(404, "not found"),
+        }
+    }
+
+    fn list(&self) -> Reply {
+        match self.manager.list_movies() {
+            Ok(movies) => Reply::json(200, json!(movies)),
+            Err(e) => error_reply(e),
+        }
+    }
+
+    fn create(&self, body: &str) -> Reply {
+        let movie: Movie = match serde_json::from_str(body) {
+            Ok(movie) => movie,
+            Err(e) => return Reply::error(400, format!("invalid movie: {}", e)),
+        };
+        match self.manager.create_movie(
+            movie.id,
+            movie.title.clone(),
+            movie.director.clone(),
+            movie.release_date.clone(),
+        ) {
+            // 返回实际保存的记录：导演别名已解析为规范名称，文本已归一化
+            Ok(()) => match self.manager.read_movie(movie.id) {
+                Ok(Some(stored)) => Reply::json(201, json!(stored)),
+                Ok(None) => not_found(movie.id),
+                Err(e) => error_reply(e),
+            },
+            Err(e) => write_error_reply(e, movie.id),
+        }
+    }
+
+    fn read(&self, id: u32) -> Reply {
+        match self.manager.read_movie(id) {
+            Ok(Some(movie)) => Reply::json(200, json!(movie)),
+            Ok(None) => not_found(id),
+            Err(e) => error_reply(e),
+        }
+    }
+
+    fn update(&self, id: u32, body: &str) -> Reply {
+        let patch: MoviePatch = match serde_json::from_str(body) {
+            Ok(patch) => patch,
+            Err(e) => return Reply::error(400, format!("invalid update: {}", e)),
+        };
+        match self
+            .manager
+            .update_movie(id, patch.title, patch.director, patch.release_date)
+        {
+            Ok(true) => self.read(id),
+            Ok(false) => not_found(id),
+            Err(e) => write_error_reply(e, id),
+        }
+    }
+
+    fn delete(&self, id: u32) -> Reply {
+        match self.manager.delete_movie(id) {
+            Ok(true) => Reply::empty(204),
+            Ok(false) => not_found(id),
+            Err(e) => error_reply(e),
+        }
+    }
+}
+
+fn not_found(id: u32) -> Reply {
+    Reply::error(404, format!("movie with ID {} not found", id))
+}
+
+/// 写入时的错误：唯一约束冲突按冲突的索引返回 409，区分ID重复和自然键重复；其余交给 `error_reply`。
+fn write_error_reply(e: Error, id: u32) -> Reply {
+    match &e {
+        Error::Sqlite(rusqlite::Error::SqliteFailure(failure, message))
+            if failure.code == ErrorCode::ConstraintViolation =>
+        {
+            let message = message.as_deref().unwrap_or_default();
+            if message.contains(NATURAL_KEY_INDEX) {
+                Reply::error(
+                    409,
+                    "another movie already has the same title, director and release year",
+                )
+            } else if message.contains("movies.id") {
+                Reply::error(409, format!("movie with ID {} already exists", id))
+            } else {
+                Reply::error(409, format!("conflict: {}", message))
+            }
+        }
+        _ => error_reply(e),
+    }
+}
+
+fn error_reply(e: Error) -> Reply {
+    match e {
+        Error::Validation(v) => {
+            let fields: Vec<_> = v
+                .errors
+                .iter()
+                .map(|f| json!({ "field": f.field, "message": f.message }))
+                .collect();
+            Reply::json(422, json!({ "error": "invalid movie", "fields": fields }))
+        }
+        Error::NotFound(message) => Reply::error(404, message),
+        Error::Conflict(message) => Reply::error(409, message),
+        Error::Sqlite(e) => Reply::error(500, format!("storage error: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    /// 在临时数据库上启动服务，返回监听地址和临时目录（需保持存活）。
+    fn start() -> (SocketAddr, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = MovieManager::new(db.to_str().unwrap()).unwrap();
+        let server = MovieServer::bind("127.0.0.1:0", manager).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        (addr, dir)
+    }
+
+    /// 发送一个最小的 HTTP/1.1 请求，返回状态码和响应体。
+    fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            addr,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, b)| b.to_string())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    #[test]
+    fn test_rest_crud() {
+        let (addr, _dir) = start();
+        let inception = r#"{"id":1,"title":"Inception","director":"Christopher Nolan","release_date":"2010/07/16"}"#;
+
+        assert_eq!(send(addr, "POST", "/movies", inception).0, 201);
+        assert_eq!(send(addr, "POST", "/movies", inception).0, 409);
+
+        let (status, body) = send(addr, "GET", "/movies/1", "");
+        assert_eq!(status, 200);
+        let movie: Movie = serde_json::from_str(&body).unwrap();
+        assert_eq!(movie.title, "Inception");
+
+        let (status, body) = send(addr, "PATCH", "/movies/1", r#"{"title":"Inception 2"}"#);
+        assert_eq!(status, 200);
+        let movie: Movie = serde_json::from_str(&body).unwrap();
+        assert_eq!(movie.title, "Inception 2");
+        assert_eq!(movie.director, "Christopher Nolan");
+
+        let (status, body) = send(addr, "GET", "/movies", "");
+        assert_eq!(status, 200);
+        let movies: Vec<Movie> = serde_json::from_str(&body).unwrap();
+        assert_eq!(movies.len(), 1);
+
+        assert_eq!(send(addr, "DELETE", "/movies/1", "").0, 204);
+        assert_eq!(send(addr, "GET", "/movies/1", "").0, 404);
+        assert_eq!(send(addr, "DELETE", "/movies/1", "").0, 404);
+    }
+
+    #[test]
+    fn test_rest_bad_requests() {
+        let (addr, _dir) = start();
+        assert_eq!(send(addr, "GET", "/movies/abc", "").0, 400);
+        assert_eq!(send(addr, "POST", "/movies", "{not json").0, 400);
+        assert_eq!(send(addr, "PATCH", "/movies/1", r#"{"title":"X"}"#).0, 404);
+        assert_eq!(send(addr, "PUT", "/movies", "").0, 405);
+        let blank = r#"{"id":2,"title":" ","director":"X","release_date":"banana"}"#;
+        let (status, body) = send(addr, "POST", "/movies", blank);
+        assert_eq!(status, 422);
+        assert!(body.contains("release_date"));
+        assert_eq!(send(addr, "GET", "/unknown", "").0, 404);
+    }
+
+    #[test]
+    fn test_rest_natural_key_conflict() {
+        let (addr, dir) = start();
+        let manager = MovieManager::new(dir.path().join("movies.db").to_str().unwrap()).unwrap();
+        manager.set_natural_key_unique(true).unwrap();
+
+        let inception = r#"{"id":1,"title":"Inception","director":"Christopher Nolan","release_date":"2010/07/16"}"#;
+        assert_eq!(send(addr, "POST", "/movies", inception).0, 201);
+        manager
+            .add_director_alias("Christopher Nolan", "C. Nolan")
+            .unwrap();
+        let (status, body) = send(addr, "POST", "/movies", inception);
+        assert_eq!(status, 409);
+        assert!(body.contains("ID 1 already exists"));
+
+        let copy = r#"{"id":2,"title":"INCEPTION","director":"Christopher Nolan","release_date":"2010/01/01"}"#;
+        let (status, body) = send(addr, "POST", "/movies", copy);
+        assert_eq!(status, 409);
+        assert!(body.contains("same title, director and release year"));
+
+        // 201 的响应体是保存后的记录，别名已解析
+        let tenet = r#"{"id":3,"title":"Tenet","director":"C. Nolan","release_date":"2010/08/26"}"#;
+        let (status, body) = send(addr, "POST", "/movies", tenet);
+        assert_eq!(status, 201);
+        let movie: Movie = serde_json::from_str(&body).unwrap();
+        assert_eq!(movie.director, "Christopher Nolan");
+        let (status, body) = send(addr, "PATCH", "/movies/3", r#"{"title":"Inception"}"#);
+        assert_eq!(status, 409);
+        assert!(body.contains("same title, director and release year"));
+    }
+}
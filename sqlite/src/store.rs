@@ -0,0 +1,575 @@
+//! 存储后端抽象。
+//!
+//! `MovieStore` 描述电影目录需要的全部操作，`MovieManager`（SQLite）和
+//! `MemoryStore`（内存 `HashMap`）都实现了它，应用代码可以只依赖该 trait。
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{
+    Field, FuzzyMatch, Movie, MovieFilter, MovieManager, MovieRecord, UpsertOutcome,
+    ValidationError, ValidationRules,
+};
+
+/// 电影存储后端。
+pub trait MovieStore {
+    /// 后端特有的错误类型。
+    type Error: Error + Send + Sync + 'static;
+
+    /// 添加一部新的电影。
+    fn create_movie(
+        &self,
+        id: u32,
+        title: String,
+        director: String,
+        release_date: String,
+    ) -> Result<(), Self::Error>;
+
+    /// 根据ID读取一部电影。
+    fn read_movie(&self, id: u32) -> Result<Option<Movie>, Self::Error>;
+
+    /// 更新一部电影，电影不存在时返回 `false`。
+    fn update_movie(
+        &self,
+        id: u32,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<bool, Self::Error>;
+
+    /// 删除一部电影，电影不存在时返回 `false`。
+    fn delete_movie(&self, id: u32) -> Result<bool, Self::Error>;
+
+    /// 按ID顺序列出所有电影。
+    fn list_movies(&self) -> Result<Vec<Movie>, Self::Error>;
+
+    /// 按ID顺序列出满足条件的电影。
+    fn search_movies(&self, filter: &MovieFilter) -> Result<Vec<Movie>, Self::Error>;
+
+    /// 根据ID读取一部电影的部分字段。
+    ///
+    /// 默认实现读取整部电影后再取出字段；能只查询部分列的后端应当覆盖它。
+    fn read_fields(&self, id: u32, fields: &[Field]) -> Result<Option<MovieRecord>, Self::Error> {
+        Ok(self
+            .read_movie(id)?
+            .map(|movie| MovieRecord::from_movie(&movie, fields)))
+    }
+
+    /// 按ID顺序列出满足条件的电影的部分字段。
+    fn search_fields(
+        &self,
+        filter: &MovieFilter,
+        fields: &[Field],
+    ) -> Result<Vec<MovieRecord>, Self::Error> {
+        Ok(self
+            .search_movies(filter)?
+            .iter()
+            .map(|movie| MovieRecord::from_movie(movie, fields))
+            .collect())
+    }
+
+    /// 按相似度搜索电影（见 `fuzzy` 模块），返回得分最高的 `limit` 部。
+    ///
+    /// 默认实现只比较标题和导演；支持别名标题的后端应当覆盖它。
+    fn fuzzy_search(&self, query: &str, limit: usize) -> Result<Vec<FuzzyMatch>, Self::Error> {
+        Ok(crate::fuzzy::rank(self.list_movies()?, &[], query, limit))
+    }
+
+    /// ID与 `id` 最接近的至多 `limit` 部电影，按距离再按ID排列，用于“did you mean”建议。
+    ///
+    /// 默认实现读取全部电影后排序；能在查询中排序的后端应当覆盖它。
+    fn nearest_movies(&self, id: u32, limit: usize) -> Result<Vec<Movie>, Self::Error> {
+        let mut movies = self.list_movies()?;
+        movies.sort_by_key(|m| (m.id.abs_diff(id), m.id));
+        movies.truncate(limit);
+        Ok(movies)
+    }
+
+    /// 每部电影在首选语言下显示的标题（见 `titles` 模块）。
+    ///
+    /// 默认实现返回空表，即不支持别名标题的后端总是显示主标题。
+    fn localized_titles(&self, _lang: &str) -> Result<HashMap<u32, String>, Self::Error> {
+        Ok(HashMap::new())
+    }
+
+    /// 插入一部电影，或更新按ID或自然键匹配到的已有电影。
+    ///
+    /// 默认实现基于其他方法，不保证原子性；支持事务的后端应当覆盖它。
+    fn upsert_movie(&self, movie: Movie) -> Result<UpsertOutcome, Self::Error> {
+        let existing = match self.read_movie(movie.id)? {
+            Some(found) => Some(found.id),
+            None => {
+                let key = movie.natural_key();
+                self.list_movies()?
+                    .into_iter()
+                    .find(|m| m.natural_key() == key)
+                    .map(|m| m.id)
+            }
+        };
+        match existing {
+            Some(id) => {
+                self.update_movie(
+                    id,
+                    Some(movie.title),
+                    Some(movie.director),
+                    Some(movie.release_date),
+                )?;
+                Ok(UpsertOutcome::Updated(id))
+            }
+            None => {
+                self.create_movie(movie.id, movie.title, movie.director, movie.release_date)?;
+                Ok(UpsertOutcome::Inserted)
+            }
+        }
+    }
+
+    /// 更新所有满足条件的电影，返回更新的数量。
+    ///
+    /// 默认实现逐条更新，不保证原子性；支持事务的后端应当覆盖它。
+    fn update_where(
+        &self,
+        filter: &MovieFilter,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<usize, Self::Error> {
+        if !filter.has_criteria() {
+            return Ok(0);
+        }
+        let movies = self.search_movies(filter)?;
+        for movie in &movies {
+            self.update_movie(
+                movie.id,
+                title.clone(),
+                director.clone(),
+                release_date.clone(),
+            )?;
+        }
+        Ok(movies.len())
+    }
+
+    /// 删除所有满足条件的电影，返回删除的数量。
+    fn delete_where(&self, filter: &MovieFilter) -> Result<usize, Self::Error> {
+        if !filter.has_criteria() {
+            return Ok(0);
+        }
+        let movies = self.search_movies(filter)?;
+        for movie in &movies {
+            self.delete_movie(movie.id)?;
+        }
+        Ok(movies.len())
+    }
+}
+
+impl MovieStore for MovieManager {
+    type Error = crate::Error;
+
+    fn create_movie(
+        &self,
+        id: u32,
+        title: String,
+        director: String,
+        release_date: String,
+    ) -> crate::Result<()> {
+        MovieManager::create_movie(self, id, title, director, release_date)
+    }
+
+    fn read_movie(&self, id: u32) -> crate::Result<Option<Movie>> {
+        MovieManager::read_movie(self, id)
+    }
+
+    fn update_movie(
+        &self,
+        id: u32,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> crate::Result<bool> {
+        MovieManager::update_movie(self, id, title, director, release_date)
+    }
+
+    fn delete_movie(&self, id: u32) -> crate::Result<bool> {
+        MovieManager::delete_movie(self, id)
+    }
+
+    fn list_movies(&self) -> crate::Result<Vec<Movie>> {
+        MovieManager::list_movies(self)
+    }
+
+    fn search_movies(&self, filter: &MovieFilter) -> crate::Result<Vec<Movie>> {
+        MovieManager::search_movies(self, filter)
+    }
+
+    fn read_fields(&self, id: u32, fields: &[Field]) -> crate::Result<Option<MovieRecord>> {
+        MovieManager::read_fields(self, id, fields)
+    }
+
+    fn search_fields(
+        &self,
+        filter: &MovieFilter,
+        fields: &[Field],
+    ) -> crate::Result<Vec<MovieRecord>> {
+        MovieManager::search_fields(self, filter, fields)
+    }
+
+    fn fuzzy_search(&self, query: &str, limit: usize) -> crate::Result<Vec<FuzzyMatch>> {
+        MovieManager::fuzzy_search(self, query, limit)
+    }
+
+    fn localized_titles(&self, lang: &str) -> crate::Result<HashMap<u32, String>> {
+        MovieManager::localized_titles(self, lang)
+    }
+
+    fn nearest_movies(&self, id: u32, limit: usize) -> crate::Result<Vec<Movie>> {
+        MovieManager::nearest_movies(self, id, limit)
+    }
+
+    fn upsert_movie(&self, movie: Movie) -> crate::Result<UpsertOutcome> {
+        MovieManager::upsert_movie(self, movie)
+    }
+
+    fn update_where(
+        &self,
+        filter: &MovieFilter,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> crate::Result<usize> {
+        MovieManager::update_where(self, filter, title, director, release_date)
+    }
+
+    fn delete_where(&self, filter: &MovieFilter) -> crate::Result<usize> {
+        MovieManager::delete_where(self, filter)
+    }
+}
+
+/// 内存存储的错误。
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryStoreError {
+    /// 该ID的电影已存在。
+    DuplicateId(u32),
+    /// 已有另一部标题、导演和上映年份相同的电影（附带其ID）。
+    DuplicateNaturalKey(u32),
+    /// 电影字段未通过校验。
+    Validation(ValidationError),
+}
+
+impl fmt::Display for MemoryStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryStoreError::DuplicateId(id) => write!(f, "movie with ID {} already exists", id),
+            MemoryStoreError::DuplicateNaturalKey(id) => write!(
+                f,
+                "movie with ID {} already has the same title, director and year",
+                id
+            ),
+            MemoryStoreError::Validation(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for MemoryStoreError {}
+
+impl From<ValidationError> for MemoryStoreError {
+    fn from(e: ValidationError) -> Self {
+        MemoryStoreError::Validation(e)
+    }
+}
+
+/// 基于 `HashMap` 的内存存储，适合单元测试。
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    movies: RefCell<HashMap<u32, Movie>>,
+    rules: ValidationRules,
+    natural_key_unique: bool,
+}
+
+impl MemoryStore {
+    /// 创建一个空的内存存储。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置创建和更新时使用的校验规则。
+    pub fn with_rules(mut self, rules: ValidationRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// 开启或关闭自然键唯一约束。
+    pub fn with_natural_key_unique(mut self, enabled: bool) -> Self {
+        self.natural_key_unique = enabled;
+        self
+    }
+
+    /// 约束开启时，检查是否有其他电影与其自然键相同。
+    fn check_natural_key(
+        &self,
+        movies: &HashMap<u32, Movie>,
+        movie: &Movie,
+    ) -> Result<(), MemoryStoreError> {
+        if !self.natural_key_unique {
+            return Ok(());
+        }
+        let key = movie.natural_key();
+        match movies
+            .values()
+            .find(|other| other.id != movie.id && other.natural_key() == key)
+        {
+            Some(other) => Err(MemoryStoreError::DuplicateNaturalKey(other.id)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl MovieStore for MemoryStore {
+    type Error = MemoryStoreError;
+
+    fn create_movie(
+        &self,
+        id: u32,
+        title: String,
+        director: String,
+        release_date: String,
+    ) -> Result<(), MemoryStoreError> {
+        let movie = Movie::new(id, title, director, release_date).normalized();
+        movie.validate(&self.rules)?;
+        let mut movies = self.movies.borrow_mut();
+        if movies.contains_key(&id) {
+            return Err(MemoryStoreError::DuplicateId(id));
+        }
+        self.check_natural_key(&movies, &movie)?;
+        movies.insert(id, movie);
+        Ok(())
+    }
+
+    fn read_movie(&self, id: u32) -> Result<Option<Movie>, MemoryStoreError> {
+        Ok(self.movies.borrow().get(&id).cloned())
+    }
+
+    fn update_movie(
+        &self,
+        id: u32,
+        title: Option<String>,
+        director: Option<String>,
+        release_date: Option<String>,
+    ) -> Result<bool, MemoryStoreError> {
+        let mut movies = self.movies.borrow_mut();
+        let mut movie = match movies.get(&id) {
+            Some(movie) => movie.clone(),
+            None => return Ok(false),
+        };
+        if let Some(title) = title {
+            movie.title = title;
+        }
+        if let Some(director) = director {
+            movie.director = director;
+        }
+        if let Some(release_date) = release_date {
+            movie.release_date = release_date;
+        }
+        let movie = movie.normalized();
+        movie.validate(&self.rules)?;
+        self.check_natural_key(&movies, &movie)?;
+        movies.insert(id, movie);
+        Ok(true)
+    }
+
+    fn delete_movie(&self, id: u32) -> Result<bool, MemoryStoreError> {
+        Ok(self.movies.borrow_mut().remove(&id).is_some())
+    }
+
+    fn list_movies(&self) -> Result<Vec<Movie>, MemoryStoreError> {
+        self.search_movies(&MovieFilter::default())
+    }
+
+    fn search_movies(&self, filter: &MovieFilter) -> Result<Vec<Movie>, MemoryStoreError> {
+        let mut movies: Vec<Movie> = self
+            .movies
+            .borrow()
+            .values()
+            .filter(|movie| filter.matches(movie))
+            .cloned()
+            .collect();
+        movies.sort_by(|a, b| filter.compare(a, b));
+        Ok(movies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 对任意存储后端运行同一组CRUD检查。
+    fn exercise<S: MovieStore>(store: &S) {
+        store
+            .create_movie(
+                2,
+                "Interstellar".into(),
+                "Christopher Nolan".into(),
+                "2014/11/5".into(),
+            )
+            .unwrap();
+        store
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        assert!(store
+            .create_movie(1, "Duplicate".into(), "Nobody".into(), "2000/1/1".into())
+            .is_err());
+        assert!(store
+            .create_movie(3, " ".into(), "Nobody".into(), "banana".into())
+            .is_err());
+        assert!(store.update_movie(1, Some("".into()), None, None).is_err());
+
+        assert_eq!(store.read_movie(1).unwrap().unwrap().title, "Inception");
+        assert!(store.read_movie(3).unwrap().is_none());
+
+        assert!(store
+            .update_movie(1, None, Some("C. Nolan".into()), None)
+            .unwrap());
+        assert!(!store
+            .update_movie(3, Some("Missing".into()), None, None)
+            .unwrap());
+        assert_eq!(store.read_movie(1).unwrap().unwrap().director, "C. Nolan");
+
+        let ids: Vec<u32> = store.list_movies().unwrap().iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        let nearest: Vec<u32> = store
+            .nearest_movies(1917, 1)
+            .unwrap()
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(nearest, vec![2]);
+
+        let filter = MovieFilter::parse(&["director=christopher"]).unwrap();
+        let found = store.search_movies(&filter).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, 2);
+
+        assert!(store.delete_movie(2).unwrap());
+        assert!(!store.delete_movie(2).unwrap());
+        assert_eq!(store.list_movies().unwrap().len(), 1);
+
+        // 按ID匹配、按自然键匹配、以及插入新记录
+        let upsert = |id: u32, title: &str, director: &str, date: &str| {
+            store
+                .upsert_movie(Movie::new(id, title.into(), director.into(), date.into()))
+                .unwrap()
+        };
+        assert_eq!(
+            upsert(1, "Inception", "Christopher Nolan", "2010/7/16"),
+            UpsertOutcome::Updated(1)
+        );
+        assert_eq!(
+            upsert(9, " inception ", "CHRISTOPHER NOLAN", "2010/1/1"),
+            UpsertOutcome::Updated(1)
+        );
+        assert_eq!(
+            upsert(9, "Tenet", "Christopher Nolan", "2020/8/26"),
+            UpsertOutcome::Inserted
+        );
+        assert_eq!(store.list_movies().unwrap().len(), 2);
+
+        // 按条件批量更新和删除
+        let nolan = MovieFilter::parse(&["director=nolan"]).unwrap();
+        assert_eq!(
+            store
+                .update_where(&nolan, None, Some("C. Nolan".into()), None)
+                .unwrap(),
+            2
+        );
+        assert!(store
+            .update_where(&nolan, Some(" ".into()), None, None)
+            .is_err());
+        // 只有排序的过滤条件没有匹配条件，不会更新或删除任何电影
+        let sort_only = MovieFilter::parse(&["sort=id"]).unwrap();
+        assert_eq!(store.delete_where(&sort_only).unwrap(), 0);
+        assert_eq!(
+            store
+                .update_where(&sort_only, Some("X".into()), None, None)
+                .unwrap(),
+            0
+        );
+        assert_eq!(store.list_movies().unwrap().len(), 2);
+        let year = MovieFilter::parse(&["year=2020"]).unwrap();
+        assert_eq!(store.delete_where(&year).unwrap(), 1);
+        assert_eq!(store.list_movies().unwrap().len(), 1);
+    }
+
+    /// 开启自然键唯一约束后，重复的电影无法创建。
+    fn exercise_natural_key<S: MovieStore>(store: &S) {
+        store
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        assert!(store
+            .create_movie(
+                2,
+                "INCEPTION".into(),
+                "christopher nolan".into(),
+                "2010/1/1".into()
+            )
+            .is_err());
+        store
+            .create_movie(
+                3,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2011/1/1".into(),
+            )
+            .unwrap();
+        assert!(store
+            .update_movie(3, None, None, Some("2010/2/2".into()))
+            .is_err());
+
+        // 非 ASCII 标题同样按折叠后的大小写和重音比较
+        store
+            .create_movie(4, "École".into(), "Émile Zola".into(), "2001/1/1".into())
+            .unwrap();
+        assert!(store
+            .create_movie(5, "ÉCOLE".into(), "Émile Zola".into(), "2001/6/1".into())
+            .is_err());
+        let upsert = Movie::new(5, "ECOLE".into(), "Émile Zola".into(), "2001/1/1".into());
+        assert_eq!(
+            store.upsert_movie(upsert).unwrap(),
+            UpsertOutcome::Updated(4)
+        );
+        assert_eq!(store.read_movie(4).unwrap().unwrap().title, "ECOLE");
+    }
+
+    #[test]
+    fn test_memory_store() {
+        exercise(&MemoryStore::new());
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        exercise(&MovieManager::new(db.to_str().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_natural_key_unique() {
+        exercise_natural_key(&MemoryStore::new().with_natural_key_unique(true));
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = MovieManager::new(db.to_str().unwrap()).unwrap();
+        manager.set_natural_key_unique(true).unwrap();
+        assert!(manager.natural_key_unique().unwrap());
+        exercise_natural_key(&manager);
+    }
+}
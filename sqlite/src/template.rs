@@ -0,0 +1,223 @@
+//! 用模板输出电影（`--template` 和 `--template-file`）。
+//!
+//! 模板使用 Jinja 语法（minijinja）。可用的字段有 `id`、`title`、`director`、`release_date`，
+//! （指定了 `--fields` 时只有选中的字段），以及从 1 开始的 `index` 和电影总数 `count`；除内置过滤器（如 `upper`）外还提供 `date` 过滤器。
+//! 模板中定义了 `row` 块时，`header` 和 `footer` 块分别在所有电影之前和之后输出一次，
+//! 其中可以使用 `count` 和 `movies`；否则整个模板就是每部电影的格式。
+
+use std::io::{self, Write};
+
+use minijinja::value::Serde;
+use minijinja::{context, Environment, ErrorKind as TemplateErrorKind, Value};
+use sqlite_movies::validation::parse_release_date;
+use sqlite_movies::MovieRecord;
+
+use crate::cli_error::{CliError, ErrorKind};
+
+const NAME: &str = "movie";
+
+/// 编译好的输出模板。
+pub struct MovieTemplate {
+    env: Environment<'static>,
+    /// 是否定义了 `row` 块。
+    has_blocks: bool,
+}
+
+impl MovieTemplate {
+    /// 编译模板，语法错误视为无效输入。
+    pub fn new(source: String) -> Result<Self, CliError> {
+        let mut env = Environment::new();
+        // 块标签独占一行时不留下空行，便于在模板文件中逐行书写
+        env.set_syntax(
+            minijinja::syntax::SyntaxConfig::builder()
+                .trim_blocks(true)
+                .lstrip_blocks(true)
+                .build()
+                .expect("the default delimiters are valid"),
+        );
+        env.add_filter("date", date);
+        env.add_template_owned(NAME, source)
+            .map_err(template_error)?;
+
+        let mut template = MovieTemplate {
+            env,
+            has_blocks: false,
+        };
+        template.has_blocks = template.render_block("row", context! {})?.is_some();
+        Ok(template)
+    }
+
+    /// 按模板输出电影列表。
+    pub fn print(&self, movies: &[MovieRecord]) -> Result<(), CliError> {
+        let mut out = io::stdout().lock();
+        let count = movies.len();
+        if self.has_blocks {
+            if let Some(text) =
+                self.render_block("header", context! { count, movies => Serde(movies) })?
+            {
+                write_section(&mut out, &text)?;
+            }
+        }
+        for (i, movie) in movies.iter().enumerate() {
+            let ctx = context! { index => i + 1, count, ..Value::from(Serde(movie)) };
+            let text = if self.has_blocks {
+                self.render_block("row", ctx)?.unwrap_or_default()
+            } else {
+                self.env
+                    .get_template(NAME)
+                    .and_then(|t| t.render(ctx))
+                    .map_err(template_error)?
+            };
+            write_section(&mut out, &text)?;
+        }
+        if self.has_blocks {
+            if let Some(text) =
+                self.render_block("footer", context! { count, movies => Serde(movies) })?
+            {
+                write_section(&mut out, &text)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 只渲染模板中的一个块，块不存在时返回 `None`。
+    fn render_block(&self, block: &str, ctx: Value) -> Result<Option<String>, CliError> {
+        let template = self.env.get_template(NAME).map_err(template_error)?;
+        let mut captured = template.render_captured(ctx).map_err(template_error)?;
+        match captured.with_state_mut(|state| state.render_block(block)) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) if e.kind() == TemplateErrorKind::UnknownBlock => Ok(None),
+            Err(e) => Err(template_error(e)),
+        }
+    }
+}
+
+/// 输出一段渲染结果，没有以换行结尾时补上换行。
+fn write_section(out: &mut impl Write, text: &str) -> Result<(), CliError> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    let newline = if text.ends_with('\n') { "" } else { "\n" };
+    write!(out, "{}{}", text, newline).map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))
+}
+
+fn template_error(error: minijinja::Error) -> CliError {
+    CliError::new(
+        ErrorKind::InvalidInput,
+        format!("template error: {}", error),
+    )
+}
+
+/// `date` 过滤器：按 `format` 格式化上映日期，默认 `%Y-%m-%d`。
+///
+/// 支持 `%Y`、`%m`、`%d`、`%-m`、`%-d`、`%B`（英文月份）、`%b`（缩写）和 `%%`；无法解析的日期原样返回。
+fn date(value: String, format: Option<String>) -> String {
+    const MONTHS: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ];
+    let Some((year, month, day)) = parse_release_date(&value) else {
+        return value;
+    };
+    let format = format.as_deref().unwrap_or("%Y-%m-%d");
+    let month_name = MONTHS[month as usize - 1];
+    let mut result = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => result.push_str(&year.to_string()),
+            Some('m') => result.push_str(&format!("{:02}", month)),
+            Some('d') => result.push_str(&format!("{:02}", day)),
+            Some('B') => result.push_str(month_name),
+            Some('b') => result.push_str(&month_name[..3]),
+            Some('%') => result.push('%'),
+            Some('-') => match chars.next() {
+                Some('m') => result.push_str(&month.to_string()),
+                Some('d') => result.push_str(&day.to_string()),
+                other => {
+                    result.push_str("%-");
+                    result.extend(other);
+                }
+            },
+            other => {
+                result.push('%');
+                result.extend(other);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlite_movies::Movie;
+
+    #[test]
+    fn test_template_sections_and_filters() {
+        assert_eq!(date("2010/7/16".into(), None), "2010-07-16");
+        assert_eq!(
+            date("2010/7/16".into(), Some("%-d %b %Y".into())),
+            "16 Jul 2010"
+        );
+        assert_eq!(date("banana".into(), None), "banana");
+
+        let movie = Movie::new(
+            1,
+            "Inception".into(),
+            "Christopher Nolan".into(),
+            "2010/7/16".into(),
+        );
+        let single =
+            MovieTemplate::new("- {{ title | upper }} ({{ release_date | date('%Y') }})".into())
+                .unwrap();
+        assert!(!single.has_blocks);
+        assert_eq!(
+            single
+                .env
+                .get_template(NAME)
+                .unwrap()
+                .render(Value::from(Serde(&movie)))
+                .unwrap(),
+            "- INCEPTION (2010)"
+        );
+
+        let table = MovieTemplate::new(
+            "{% block header %}\n| Title |\n{% endblock %}\n\
+             {% block row %}\n| {{ index }}. {{ title }} |\n{% endblock %}\n\
+             {% block footer %}{{ count }} movies{% endblock %}"
+                .into(),
+        )
+        .unwrap();
+        assert!(table.has_blocks);
+        let row = context! { index => 1, count => 1, ..Value::from(Serde(&movie)) };
+        assert_eq!(
+            table.render_block("row", row).unwrap().unwrap(),
+            "| 1. Inception |\n"
+        );
+        let movies = vec![movie];
+        assert_eq!(
+            table
+                .render_block("footer", context! { count => 1, movies => Serde(movies) })
+                .unwrap()
+                .unwrap(),
+            "1 movies"
+        );
+
+        assert!(MovieTemplate::new("{{ title".into()).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! 别名标题（不同语言或地区的标题）。
+//!
+//! 同一部电影在不同市场可能有不同的名称。`movies.title` 是主标题，
+//! 其余标题保存在 alternate_titles 表中，每条带有语言代码（如 `en`、`zh-TW`）和类型。
+//! 搜索会同时匹配主标题和所有别名标题；显示时可以按首选语言选出标题，没有时使用主标题。
+
+use std::collections::HashMap;
+use std::fmt;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, ToSql};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::collation::nfc;
+use crate::validation::check_text;
+use crate::{Error, FieldError, MovieManager, Result, ValidationError};
+
+/// 别名标题的类型。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleKind {
+    /// 原始语言的标题。
+    Original,
+    /// 某个市场的发行标题。
+    Localized,
+    /// 制作期间的暂定标题，不用于显示。
+    Working,
+}
+
+impl TitleKind {
+    /// 全部类型。
+    pub const ALL: [TitleKind; 3] = [
+        TitleKind::Original,
+        TitleKind::Localized,
+        TitleKind::Working,
+    ];
+
+    /// 类型名称，也是数据库中保存的值。
+    pub fn name(self) -> &'static str {
+        match self {
+            TitleKind::Original => "original",
+            TitleKind::Localized => "localized",
+            TitleKind::Working => "working",
+        }
+    }
+
+    /// 按名称解析类型。
+    pub fn parse(input: &str) -> Option<Self> {
+        TitleKind::ALL.into_iter().find(|kind| kind.name() == input)
+    }
+}
+
+impl fmt::Display for TitleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl ToSql for TitleKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.name().into())
+    }
+}
+
+impl FromSql for TitleKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        TitleKind::parse(value.as_str()?).ok_or(FromSqlError::InvalidType)
+    }
+}
+
+/// 一部电影的一个别名标题。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlternateTitle {
+    pub movie_id: u32,
+    pub title: String,
+    /// 语言或地区代码，例如 `ja`、`zh-CN`，比较时不区分大小写。
+    pub lang: String,
+    pub kind: TitleKind,
+}
+
+impl MovieManager {
+    /// 为电影添加一个别名标题。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn add_alternate_title(
+        &self,
+        movie_id: u32,
+        title: &str,
+        lang: &str,
+        kind: TitleKind,
+    ) -> Result<()> {
+        let title = nfc(title.trim());
+        let lang = lang.trim();
+        let mut errors = Vec::new();
+        check_text(&mut errors, "title", &title, self.rules.max_title_len);
+        if !is_lang_code(lang) {
+            errors.push(FieldError {
+                field: "lang",
+                message: format!("'{}' is not a language code such as en or zh-TW", lang),
+            });
+        }
+        if !errors.is_empty() {
+            return Err(Error::Validation(ValidationError { errors }));
+        }
+        if self.read_movie(movie_id)?.is_none() {
+            return Err(Error::NotFound(format!(
+                "movie with ID {} not found",
+                movie_id
+            )));
+        }
+
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO alternate_titles (movie_id, title, lang, kind, title_roman, title_initials)
+             VALUES (?1, ?2, ?3, ?4, romanize(?2), romanize_initials(?2))",
+            params![movie_id, title, lang, kind],
+        )?;
+        if inserted == 0 {
+            return Err(Error::Conflict(format!(
+                "movie {} already has the {} {} title '{}'",
+                movie_id, lang, kind, title
+            )));
+        }
+        Ok(())
+    }
+
+    /// 删除电影中与 `title` 相同的别名标题（任意语言和类型），返回删除的数量。
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn remove_alternate_title(&self, movie_id: u32, title: &str) -> Result<usize> {
+        let removed = self.conn.execute(
+            "DELETE FROM alternate_titles WHERE movie_id = ?1 AND title = ?2",
+            params![movie_id, nfc(title.trim())],
+        )?;
+        Ok(removed)
+    }
+
+    /// 列出一部电影的别名标题，按语言、类型和标题排序。
+    pub fn alternate_titles(&self, movie_id: u32) -> Result<Vec<AlternateTitle>> {
+        self.query_alternate_titles("WHERE movie_id = ?1", params![movie_id])
+    }
+
+    /// 列出所有电影的别名标题，按电影ID、语言、类型和标题排序。
+    pub fn all_alternate_titles(&self) -> Result<Vec<AlternateTitle>> {
+        self.query_alternate_titles("", [])
+    }
+
+    fn query_alternate_titles(
+        &self,
+        clause: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<AlternateTitle>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT movie_id, title, lang, kind FROM alternate_titles {}
+             ORDER BY movie_id, lower(lang), kind, title",
+            clause
+        ))?;
+        let titles = stmt
+            .query_map(params, |row| {
+                Ok(AlternateTitle {
+                    movie_id: row.get(0)?,
+                    title: row.get(1)?,
+                    lang: row.get(2)?,
+                    kind: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(titles)
+    }
+
+    /// 每部电影在首选语言下显示的标题，没有合适别名标题的电影不在结果中。
+    ///
+    /// 语言代码完全相同的优先，其次是主语言相同的（`zh-TW` 也接受 `zh` 和 `zh-HK`）；
+    /// 同等条件下发行标题优先于原始标题，暂定标题不参与。
+    pub fn localized_titles(&self, lang: &str) -> Result<HashMap<u32, String>> {
+        let lang = lang.trim();
+        let primary = lang.split('-').next().unwrap_or_default();
+        let mut stmt = self.conn.prepare(
+            "SELECT movie_id, title FROM alternate_titles
+             WHERE kind != 'working' AND (lang = ?1 OR lang = ?2 OR lang LIKE ?2 || '-%')
+             ORDER BY movie_id, lang = ?1 DESC, kind = 'localized' DESC, id",
+        )?;
+        let mut titles = HashMap::new();
+        let mut rows = stmt.query(params![lang, primary])?;
+        while let Some(row) = rows.next()? {
+            titles.entry(row.get(0)?).or_insert(row.get(1)?);
+        }
+        Ok(titles)
+    }
+}
+
+/// 语言代码：以 `-` 分隔的若干段字母或数字，第一段是 2 到 3 个字母，例如 `en`、`zh-Hant-TW`。
+fn is_lang_code(code: &str) -> bool {
+    let mut parts = code.split('-');
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|part| {
+            (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MovieFilter;
+
+    #[test]
+    fn test_alternate_titles() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MovieManager::new(dir.path().join("movies.db").to_str().unwrap()).unwrap();
+        manager
+            .create_movie(
+                1,
+                "Spirited Away".into(),
+                "Hayao Miyazaki".into(),
+                "2001/7/20".into(),
+            )
+            .unwrap();
+        manager
+            .add_alternate_title(1, "千と千尋の神隠し", "ja", TitleKind::Original)
+            .unwrap();
+        manager
+            .add_alternate_title(1, "千与千寻", "zh-CN", TitleKind::Localized)
+            .unwrap();
+        manager
+            .add_alternate_title(1, "神隱少女", "zh-TW", TitleKind::Localized)
+            .unwrap();
+        assert!(matches!(
+            manager.add_alternate_title(1, "千与千寻", "ZH-cn", TitleKind::Localized),
+            Err(Error::Conflict(_))
+        ));
+        assert!(matches!(
+            manager.add_alternate_title(1, "Title", "english", TitleKind::Localized),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            manager.add_alternate_title(2, "Title", "en", TitleKind::Localized),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(manager.alternate_titles(1).unwrap().len(), 3);
+
+        // 完全相同的语言优先，其次是主语言相同的，都没有时不在结果中
+        let title = |lang: &str| manager.localized_titles(lang).unwrap().remove(&1);
+        assert_eq!(title("zh-TW").as_deref(), Some("神隱少女"));
+        assert_eq!(title("zh-cn").as_deref(), Some("千与千寻"));
+        assert_eq!(title("ja").as_deref(), Some("千と千尋の神隠し"));
+        assert_eq!(title("fr"), None);
+
+        // 搜索同时匹配别名标题，包括罗马字
+        let ids = |terms: &[&str]| -> Vec<u32> {
+            let filter = MovieFilter::parse(terms).unwrap();
+            manager
+                .search_movies(&filter)
+                .unwrap()
+                .iter()
+                .map(|m| m.id)
+                .collect()
+        };
+        assert_eq!(ids(&["神隱"]), vec![1]);
+        assert_eq!(ids(&["title=千尋"]), vec![1]);
+        assert_eq!(ids(&["roman=qyqx"]), vec![1]);
+
+        assert_eq!(manager.remove_alternate_title(1, "神隱少女").unwrap(), 1);
+        assert_eq!(title("zh-TW").as_deref(), Some("千与千寻"));
+        // 删除电影时别名标题一起删除
+        manager.delete_movie(1).unwrap();
+        assert!(manager.all_alternate_titles().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+//! 事务与试运行（dry run）。
+//!
+//! 事务基于 SQLite 的 SAVEPOINT 实现，可以嵌套：例如在试运行中执行导入或批处理。
+//! 试运行会在事务中执行操作、比较前后的数据，然后总是回滚。
+
+use std::collections::BTreeMap;
+
+use crate::{Error, Movie, MovieManager};
+
+/// 一次操作造成的数据变化。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChangeSet {
+    /// 新增的电影。
+    pub inserted: Vec<Movie>,
+    /// 被修改的电影：修改前和修改后。
+    pub updated: Vec<(Movie, Movie)>,
+    /// 被删除的电影。
+    pub deleted: Vec<Movie>,
+}
+
+impl ChangeSet {
+    /// 比较两份按ID排列的电影列表。
+    pub fn between(before: &[Movie], after: &[Movie]) -> Self {
+        let mut changes = ChangeSet::default();
+        let after_by_id: BTreeMap<u32, &Movie> = after.iter().map(|m| (m.id, m)).collect();
+        let before_by_id: BTreeMap<u32, &Movie> = before.iter().map(|m| (m.id, m)).collect();
+
+        for old in before {
+            match after_by_id.get(&old.id) {
+                None => changes.deleted.push(old.clone()),
+                Some(new) if *new != old => changes.updated.push((old.clone(), (*new).clone())),
+                Some(_) => {}
+            }
+        }
+        for new in after {
+            if !before_by_id.contains_key(&new.id) {
+                changes.inserted.push(new.clone());
+            }
+        }
+        changes
+    }
+
+    /// 是否没有任何变化。
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+    }
+}
+
+impl MovieManager {
+    /// 在事务中执行操作：成功时提交，出错时回滚。
+    pub fn transaction<T, E: From<Error>>(
+        &self,
+        f: impl FnOnce(&Self) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.conn
+            .execute_batch("SAVEPOINT movie_tx")
+            .map_err(Error::from)?;
+        match f(self) {
+            Ok(value) => {
+                self.conn
+                    .execute_batch("RELEASE movie_tx")
+                    .map_err(Error::from)?;
+                Ok(value)
+            }
+            Err(e) => {
+                // 回滚失败时保留原始错误
+                let _ = self
+                    .conn
+                    .execute_batch("ROLLBACK TO movie_tx; RELEASE movie_tx");
+                Err(e)
+            }
+        }
+    }
+
+    /// 试运行：在事务中执行操作并记录数据变化，然后总是回滚。
+    pub fn dry_run<T, E: From<Error>>(
+        &self,
+        f: impl FnOnce(&Self) -> Result<T, E>,
+    ) -> Result<(T, ChangeSet), E> {
+        self.conn
+            .execute_batch("SAVEPOINT dry_run")
+            .map_err(Error::from)?;
+
+        let result = (|| {
+            let before = self.list_movies()?;
+            let value = f(self)?;
+            let after = self.list_movies()?;
+            Ok((value, ChangeSet::between(&before, &after)))
+        })();
+
+        self.conn
+            .execute_batch("ROLLBACK TO dry_run; RELEASE dry_run")
+            .map_err(Error::from)?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = MovieManager::new(db.to_str().unwrap()).unwrap();
+        manager
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        manager
+            .create_movie(
+                2,
+                "Interstellar".into(),
+                "Christopher Nolan".into(),
+                "2014/11/5".into(),
+            )
+            .unwrap();
+
+        let ((), changes) = manager
+            .dry_run(|m| -> crate::Result<()> {
+                m.create_movie(
+                    3,
+                    "Tenet".into(),
+                    "Christopher Nolan".into(),
+                    "2020/8/26".into(),
+                )?;
+                m.update_movie(1, Some("Inception 2".into()), None, None)?;
+                m.delete_movie(2)?;
+                // 嵌套事务也会随试运行一起回滚
+                m.transaction(|m| m.delete_movie(3).map(|_| ()))
+            })
+            .unwrap();
+
+        assert!(changes.inserted.is_empty());
+        assert_eq!(changes.updated.len(), 1);
+        assert_eq!(changes.updated[0].1.title, "Inception 2");
+        assert_eq!(changes.deleted.len(), 1);
+        assert_eq!(manager.list_movies().unwrap().len(), 2);
+        assert_eq!(manager.read_movie(1).unwrap().unwrap().title, "Inception");
+
+        // 变化中是实际会保存的记录：导演别名已解析，文本已归一化
+        manager
+            .add_director_alias("Christopher Nolan", "C. Nolan")
+            .unwrap();
+        let ((), changes) = manager
+            .dry_run(|m| {
+                m.create_movie(
+                    3,
+                    "Ame\u{301}lie".into(),
+                    "C. Nolan".into(),
+                    "2001/4/25".into(),
+                )
+            })
+            .unwrap();
+        assert_eq!(changes.inserted[0].title, "Am\u{e9}lie");
+        assert_eq!(changes.inserted[0].director, "Christopher Nolan");
+    }
+}
@@ -0,0 +1,635 @@
+//! 终端界面：浏览、搜索和编辑电影。
+//!
+//! 界面状态 `App` 与绘制分开，按键处理不依赖终端，可以直接测试。
+
+use std::error::Error;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Flex, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, Clear, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use sqlite_movies::collation::unicode_cmp;
+use sqlite_movies::validation::parse_release_date;
+use sqlite_movies::{Movie, MovieFilter, MovieManager};
+
+/// 表单中各字段的名称，顺序与 `Form::fields` 一致。
+const FIELD_NAMES: [&str; 4] = ["ID", "Title", "Director", "Release date"];
+
+/// 启动终端界面，直到用户退出。
+pub fn run(manager: &MovieManager) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(manager)?;
+    // ratatui::init 会在 panic 时恢复终端
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), Box<dyn Error>> {
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 表格的排序列。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Id,
+    Title,
+    Director,
+    ReleaseDate,
+}
+
+impl SortKey {
+    /// 下一个排序列，循环切换。
+    fn next(self) -> Self {
+        match self {
+            SortKey::Id => SortKey::Title,
+            SortKey::Title => SortKey::Director,
+            SortKey::Director => SortKey::ReleaseDate,
+            SortKey::ReleaseDate => SortKey::Id,
+        }
+    }
+
+    /// 对应的列下标。
+    fn column(self) -> usize {
+        self as usize
+    }
+}
+
+/// 当前的交互模式。
+enum Mode {
+    Browse,
+    Search,
+    Form(Form),
+    ConfirmDelete(Movie),
+}
+
+/// 新建或编辑电影的表单。
+struct Form {
+    /// 编辑时为电影ID，新建时为 `None`。
+    editing: Option<u32>,
+    fields: [String; 4],
+    focus: usize,
+    error: Option<String>,
+}
+
+impl Form {
+    fn create() -> Self {
+        Form {
+            editing: None,
+            fields: Default::default(),
+            focus: 0,
+            error: None,
+        }
+    }
+
+    fn edit(movie: &Movie) -> Self {
+        Form {
+            editing: Some(movie.id),
+            fields: [
+                movie.id.to_string(),
+                movie.title.clone(),
+                movie.director.clone(),
+                movie.release_date.clone(),
+            ],
+            // 编辑时ID不可修改
+            focus: 1,
+            error: None,
+        }
+    }
+
+    /// 移动焦点，编辑时跳过ID字段。
+    fn move_focus(&mut self, forward: bool) {
+        let first = if self.editing.is_some() { 1 } else { 0 };
+        let count = FIELD_NAMES.len() - first;
+        let offset = self.focus - first;
+        let offset = if forward {
+            (offset + 1) % count
+        } else {
+            (offset + count - 1) % count
+        };
+        self.focus = first + offset;
+    }
+}
+
+/// 终端界面的状态。
+struct App<'a> {
+    manager: &'a MovieManager,
+    /// 全部电影，按当前排序排列。
+    movies: Vec<Movie>,
+    /// 满足搜索条件的电影在 `movies` 中的下标。
+    visible: Vec<usize>,
+    table: TableState,
+    sort: SortKey,
+    descending: bool,
+    query: String,
+    mode: Mode,
+    /// 状态栏消息，下一次按键后清除。
+    status: Option<String>,
+    /// 表格一页能显示的行数，绘制时更新。
+    page_size: usize,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(manager: &'a MovieManager) -> sqlite_movies::Result<Self> {
+        let mut app = App {
+            manager,
+            movies: Vec::new(),
+            visible: Vec::new(),
+            table: TableState::default(),
+            sort: SortKey::Id,
+            descending: false,
+            query: String::new(),
+            mode: Mode::Browse,
+            status: None,
+            page_size: 10,
+            quit: false,
+        };
+        app.movies = manager.list_movies()?;
+        app.refilter();
+        Ok(app)
+    }
+
+    /// 当前选中的电影。
+    fn selected(&self) -> Option<&Movie> {
+        let index = *self.visible.get(self.table.selected()?)?;
+        self.movies.get(index)
+    }
+
+    /// 重新读取所有电影，并尽量选中指定ID的电影。
+    fn reload(&mut self, select: Option<u32>) {
+        match self.manager.list_movies() {
+            Ok(movies) => self.movies = movies,
+            Err(e) => self.status = Some(e.to_string()),
+        }
+        self.sort_movies();
+        self.refilter();
+        if let Some(id) = select {
+            if let Some(position) = self.visible.iter().position(|&i| self.movies[i].id == id) {
+                self.table.select(Some(position));
+            }
+        }
+    }
+
+    fn sort_movies(&mut self) {
+        let key = self.sort;
+        self.movies.sort_by(|a, b| {
+            let order = match key {
+                SortKey::Id => a.id.cmp(&b.id),
+                SortKey::Title => unicode_cmp(&a.title, &b.title),
+                SortKey::Director => unicode_cmp(&a.director, &b.director),
+                // 按日期而不是字符串排序，无法解析的日期排在最后
+                SortKey::ReleaseDate => {
+                    let date =
+                        |m: &Movie| parse_release_date(&m.release_date).unwrap_or((i32::MAX, 0, 0));
+                    date(a).cmp(&date(b))
+                }
+            };
+            order.then(a.id.cmp(&b.id))
+        });
+        if self.descending {
+            self.movies.reverse();
+        }
+    }
+
+    /// 按搜索框的内容重新过滤，语法与 `search` 命令相同。
+    fn refilter(&mut self) {
+        let selected_id = self.selected().map(|m| m.id);
+        let terms: Vec<&str> = self.query.split_whitespace().collect();
+        match MovieFilter::parse(&terms) {
+            Ok(filter) => {
+                self.visible = (0..self.movies.len())
+                    .filter(|&i| filter.matches(&self.movies[i]))
+                    .collect();
+            }
+            // 输入到一半时（例如 `year=`）保留上一次的结果
+            Err(e) => self.status = Some(e),
+        }
+
+        let position = selected_id
+            .and_then(|id| self.visible.iter().position(|&i| self.movies[i].id == id))
+            .unwrap_or(0);
+        self.table.select(if self.visible.is_empty() {
+            None
+        } else {
+            Some(position)
+        });
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let last = self.visible.len() as isize - 1;
+        self.table
+            .select(Some((current + delta).clamp(0, last) as usize));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        self.status = None;
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.browse_key(key),
+            Mode::Search => self.search_key(key),
+            Mode::Form(form) => self.form_key(form, key),
+            Mode::ConfirmDelete(movie) => self.confirm_key(movie, key),
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) {
+        let page = self.page_size.max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(page),
+            KeyCode::PageUp => self.move_selection(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.reload(self.selected().map(|m| m.id));
+            }
+            KeyCode::Char('r') => {
+                self.descending = !self.descending;
+                self.reload(self.selected().map(|m| m.id));
+            }
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('n') => self.mode = Mode::Form(Form::create()),
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(movie) = self.selected() {
+                    self.mode = Mode::Form(Form::edit(movie));
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(movie) = self.selected() {
+                    self.mode = Mode::ConfirmDelete(movie.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => return,
+            KeyCode::Esc => self.query.clear(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.mode = Mode::Search;
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.mode = Mode::Search;
+            }
+            _ => {
+                self.mode = Mode::Search;
+                return;
+            }
+        }
+        self.refilter();
+    }
+
+    fn form_key(&mut self, mut form: Form, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => return,
+            KeyCode::Enter => {
+                if let Err(message) = self.save(&form) {
+                    form.error = Some(message);
+                    self.mode = Mode::Form(form);
+                }
+                return;
+            }
+            KeyCode::Tab | KeyCode::Down => form.move_focus(true),
+            KeyCode::BackTab | KeyCode::Up => form.move_focus(false),
+            KeyCode::Backspace => {
+                form.fields[form.focus].pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focus].push(c),
+            _ => {}
+        }
+        self.mode = Mode::Form(form);
+    }
+
+    /// 保存表单，失败时返回显示在表单中的错误。
+    fn save(&mut self, form: &Form) -> Result<(), String> {
+        let [id, title, director, release_date] = form.fields.clone();
+        match form.editing {
+            Some(id) => {
+                let updated = self
+                    .manager
+                    .update_movie(id, Some(title), Some(director), Some(release_date))
+                    .map_err(|e| e.to_string())?;
+                if !updated {
+                    return Err(format!("movie with ID {} not found", id));
+                }
+                self.status = Some(format!("Movie {} updated.", id));
+                self.reload(Some(id));
+            }
+            None => {
+                let id: u32 = id
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid ID '{}': expected a non-negative integer", id))?;
+                if self
+                    .manager
+                    .read_movie(id)
+                    .map_err(|e| e.to_string())?
+                    .is_some()
+                {
+                    return Err(format!("movie with ID {} already exists", id));
+                }
+                self.manager
+                    .create_movie(id, title, director, release_date)
+                    .map_err(|e| e.to_string())?;
+                self.status = Some(format!("Movie {} created.", id));
+                self.reload(Some(id));
+            }
+        }
+        Ok(())
+    }
+
+    fn confirm_key(&mut self, movie: Movie, key: KeyEvent) {
+        if let KeyCode::Char('y' | 'Y') = key.code {
+            self.status = Some(match self.manager.delete_movie(movie.id) {
+                Ok(_) => format!("Movie {} deleted.", movie.id),
+                Err(e) => e.to_string(),
+            });
+            self.reload(None);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [table_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(main_area);
+
+        self.draw_search(frame, search_area);
+        self.draw_table(frame, table_area);
+        self.draw_detail(frame, detail_area);
+
+        let status = match (&self.status, &self.mode) {
+            (Some(message), _) => message.as_str(),
+            (None, Mode::Search) => "Type to filter · Enter: keep · Esc: clear",
+            (None, Mode::Form(_)) => "Tab/↑↓: field · Enter: save · Esc: cancel",
+            (None, Mode::ConfirmDelete(_)) => "y: delete · n/Esc: cancel",
+            (None, Mode::Browse) => {
+                "↑↓/PgUp/PgDn: move · /: search · s: sort · r: reverse · n: new · e: edit · d: delete · q: quit"
+            }
+        };
+        frame.render_widget(Paragraph::new(status).dim(), status_area);
+
+        match &self.mode {
+            Mode::Form(form) => draw_form(frame, form),
+            Mode::ConfirmDelete(movie) => {
+                let area = centered(frame.area(), 50, 5);
+                let text = format!(
+                    "Delete \"{}\" (ID {})?\n\n[y] yes   [n] no",
+                    movie.title, movie.id
+                );
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(text)
+                        .centered()
+                        .wrap(Wrap { trim: true })
+                        .block(Block::bordered().title("Confirm").red()),
+                    area,
+                );
+            }
+            Mode::Browse | Mode::Search => {}
+        }
+    }
+
+    fn draw_search(&self, frame: &mut Frame, area: Rect) {
+        let searching = matches!(self.mode, Mode::Search);
+        let mut block = Block::bordered().title(format!(
+            "Search (/) · {} of {} movies",
+            self.visible.len(),
+            self.movies.len()
+        ));
+        if searching {
+            block = block.border_style(Style::new().fg(Color::Yellow));
+            frame.set_cursor_position(Position::new(
+                area.x + 1 + self.query.chars().count() as u16,
+                area.y + 1,
+            ));
+        }
+        frame.render_widget(Paragraph::new(self.query.as_str()).block(block), area);
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        // 减去边框和表头
+        self.page_size = area.height.saturating_sub(3) as usize;
+
+        let arrow = if self.descending { " ▼" } else { " ▲" };
+        let header = ["ID", "Title", "Director", "Release Date"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                if i == self.sort.column() {
+                    format!("{}{}", name, arrow)
+                } else {
+                    name.to_string()
+                }
+            });
+        let rows = self.visible.iter().map(|&i| {
+            let movie = &self.movies[i];
+            Row::new([
+                movie.id.to_string(),
+                movie.title.clone(),
+                movie.director.clone(),
+                movie.release_date.clone(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(6),
+                Constraint::Fill(3),
+                Constraint::Fill(2),
+                Constraint::Length(14),
+            ],
+        )
+        .header(Row::new(header).add_modifier(Modifier::BOLD))
+        .block(Block::bordered().title("Movies"))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let text = match self.selected() {
+            Some(movie) => Text::from(vec![
+                Line::from(movie.title.clone()).bold(),
+                Line::default(),
+                Line::from(format!("ID:           {}", movie.id)),
+                Line::from(format!("Director:     {}", movie.director)),
+                Line::from(format!("Release date: {}", movie.release_date)),
+            ]),
+            None => Text::from("No movie selected.").dim(),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title("Details")),
+            area,
+        );
+    }
+}
+
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let error_lines = form.error.as_ref().map_or(0, |e| e.lines().count() + 1);
+    let area = centered(
+        frame.area(),
+        60,
+        (FIELD_NAMES.len() + error_lines + 2) as u16,
+    );
+    let label_width = 14;
+
+    let mut lines: Vec<Line> = FIELD_NAMES
+        .iter()
+        .zip(&form.fields)
+        .enumerate()
+        .map(|(i, (name, value))| {
+            let line = Line::from(format!("{:<width$}{}", name, value, width = label_width));
+            if i == form.focus {
+                line.yellow()
+            } else if i == 0 && form.editing.is_some() {
+                line.dim()
+            } else {
+                line
+            }
+        })
+        .collect();
+    if let Some(error) = &form.error {
+        lines.push(Line::default());
+        lines.extend(error.lines().map(|l| Line::from(l.to_string()).red()));
+    }
+
+    let title = match form.editing {
+        Some(id) => format!("Edit movie {}", id),
+        None => "New movie".to_string(),
+    };
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+    frame.set_cursor_position(Position::new(
+        area.x + 1 + label_width as u16 + form.fields[form.focus].chars().count() as u16,
+        area.y + 1 + form.focus as u16,
+    ));
+}
+
+/// 在区域中居中放置指定大小的矩形。
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key(KeyEvent::from(code));
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_browse_search_and_edit() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("movies.db");
+        let manager = MovieManager::new(db.to_str().unwrap()).unwrap();
+        for (id, title, date) in [
+            (1, "Tenet", "2020/8/26"),
+            (2, "Inception", "2010/7/16"),
+            (3, "Interstellar", "2014/11/5"),
+        ] {
+            manager
+                .create_movie(id, title.into(), "Christopher Nolan".into(), date.into())
+                .unwrap();
+        }
+        let mut app = App::new(&manager).unwrap();
+
+        // 按标题排序后第一部是 Inception，按日期倒序后第一部是 Tenet
+        press(&mut app, KeyCode::Char('s'));
+        assert_eq!(app.selected().unwrap().id, 1);
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.selected().unwrap().title, "Inception");
+        press(&mut app, KeyCode::Char('s'));
+        press(&mut app, KeyCode::Char('s'));
+        press(&mut app, KeyCode::Char('r'));
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.selected().unwrap().title, "Tenet");
+
+        // 增量搜索
+        press(&mut app, KeyCode::Char('/'));
+        type_text(&mut app, "inter");
+        assert_eq!(app.visible.len(), 1);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.selected().unwrap().id, 3);
+
+        // 编辑：校验失败时表单保持打开
+        press(&mut app, KeyCode::Char('e'));
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "x");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(&app.mode, Mode::Form(form) if form.error.is_some()));
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.mode, Mode::Browse));
+
+        // 删除需要确认
+        press(&mut app, KeyCode::Char('d'));
+        press(&mut app, KeyCode::Char('n'));
+        assert!(manager.read_movie(3).unwrap().is_some());
+        press(&mut app, KeyCode::Char('d'));
+        press(&mut app, KeyCode::Char('y'));
+        assert!(manager.read_movie(3).unwrap().is_none());
+        assert!(app.visible.is_empty());
+
+        // 新建
+        press(&mut app, KeyCode::Char('/'));
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('n'));
+        for value in ["4", "Dune", "Denis Villeneuve", "2021/10/22"] {
+            type_text(&mut app, value);
+            press(&mut app, KeyCode::Tab);
+        }
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(manager.read_movie(4).unwrap().unwrap().title, "Dune");
+        assert_eq!(app.selected().unwrap().id, 4);
+        assert!(!app.quit);
+    }
+}
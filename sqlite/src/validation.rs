@@ -0,0 +1,177 @@
+//! 电影字段校验。
+//!
+//! 创建、更新和导入电影时都会调用 `Movie::validate`，所有问题会以字段级错误的形式
+//! 一次性返回，而不是只报告第一个。
+
+use std::error::Error;
+use std::fmt;
+
+use crate::Movie;
+
+/// 校验规则，可以按需调整长度和年份范围。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationRules {
+    /// 标题的最大字符数。
+    pub max_title_len: usize,
+    /// 导演的最大字符数。
+    pub max_director_len: usize,
+    /// 允许的最早上映年份。
+    pub min_year: i32,
+    /// 允许的最晚上映年份。
+    pub max_year: i32,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            max_title_len: 200,
+            max_director_len: 100,
+            // 第一部电影出现于 1888 年
+            min_year: 1888,
+            max_year: 2100,
+        }
+    }
+}
+
+/// 单个字段的校验错误。
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+/// 一部电影的全部校验错误。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid movie:")?;
+        for error in &self.errors {
+            write!(f, "\n  {}: {}", error.field, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationError {}
+
+/// 解析 `YYYY/MM/DD` 或 `YYYY-MM-DD` 格式的日期，月和日可以不补零。
+pub fn parse_release_date(input: &str) -> Option<(i32, u32, u32)> {
+    let parts: Vec<&str> = input.trim().split(['/', '-']).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let year: i32 = parts[0].parse().ok()?;
+    let month: u32 = parts[1].parse().ok()?;
+    let day: u32 = parts[2].parse().ok()?;
+
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+    Some((year, month, day))
+}
+
+impl Movie {
+    /// 按规则校验电影的各个字段。
+    pub fn validate(&self, rules: &ValidationRules) -> Result<(), ValidationError> {
+        let mut errors = Vec::new();
+
+        check_text(&mut errors, "title", &self.title, rules.max_title_len);
+        check_text(
+            &mut errors,
+            "director",
+            &self.director,
+            rules.max_director_len,
+        );
+
+        match parse_release_date(&self.release_date) {
+            None => errors.push(FieldError {
+                field: "release_date",
+                message: format!(
+                    "'{}' is not a valid date (expected YYYY/MM/DD)",
+                    self.release_date
+                ),
+            }),
+            Some((year, _, _)) if year < rules.min_year || year > rules.max_year => {
+                errors.push(FieldError {
+                    field: "release_date",
+                    message: format!(
+                        "year {} is outside {}..={}",
+                        year, rules.min_year, rules.max_year
+                    ),
+                })
+            }
+            Some(_) => {}
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { errors })
+        }
+    }
+}
+
+/// 检查文本字段去掉首尾空白后非空且不超过最大长度。
+pub(crate) fn check_text(
+    errors: &mut Vec<FieldError>,
+    field: &'static str,
+    value: &str,
+    max_len: usize,
+) {
+    let len = value.trim().chars().count();
+    if len == 0 {
+        errors.push(FieldError {
+            field,
+            message: "must not be empty".to_string(),
+        });
+    } else if len > max_len {
+        errors.push(FieldError {
+            field,
+            message: format!("must be at most {} characters (got {})", max_len, len),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let rules = ValidationRules::default();
+        let movie = Movie::new(
+            1,
+            "Inception".to_string(),
+            "Christopher Nolan".to_string(),
+            "2010/7/16".to_string(),
+        );
+        assert!(movie.validate(&rules).is_ok());
+
+        let bad = Movie::new(2, " ".to_string(), "".to_string(), "banana".to_string());
+        let fields: Vec<&str> = bad
+            .validate(&rules)
+            .unwrap_err()
+            .errors
+            .iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, vec!["title", "director", "release_date"]);
+
+        let old = Movie::new(3, "A".to_string(), "B".to_string(), "1700/1/1".to_string());
+        assert!(old.validate(&rules).is_err());
+        assert!(parse_release_date("2023/2/29").is_none());
+        assert_eq!(parse_release_date("2024-02-29"), Some((2024, 2, 29)));
+    }
+}
@@ -0,0 +1,279 @@
+//! Excel（.xlsx）导出与导入。
+//!
+//! 导出的工作表第一行是表头，上映日期写成日期单元格，列宽按内容自动调整。
+//! 导入读取第一个工作表，按表头名称（不区分大小写）找到各列，因此列的顺序和多余的列都不影响；
+//! 每一行与 CSV 导入一样经过 `MovieStore::create_movie`，失败的行记录在报告中。
+
+use std::fmt;
+use std::io::{Read, Seek};
+
+use calamine::{Data, DataType, Reader, Xlsx};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, XlsxError as WriteError};
+
+use crate::validation::parse_release_date;
+use crate::{Error, Field, ImportReport, Movie, MovieFilter, MovieManager, MovieStore};
+
+/// 导入时需要的列，表头名称与导出时相同。
+const COLUMNS: [&str; 4] = ["id", "title", "director", "release_date"];
+
+/// xlsx 读写错误。
+#[derive(Debug)]
+pub enum XlsxError {
+    /// 文件无法读取或不是有效的工作簿。
+    Read(calamine::XlsxError),
+    /// 文件无法写入。
+    Write(WriteError),
+    /// 工作簿中没有工作表。
+    NoSheet,
+    /// 表头中缺少某一列。
+    MissingColumn(&'static str),
+    /// 读取电影时的数据库错误。
+    Store(Error),
+}
+
+impl fmt::Display for XlsxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XlsxError::Read(e) => write!(f, "cannot read workbook: {}", e),
+            XlsxError::Write(e) => write!(f, "cannot write workbook: {}", e),
+            XlsxError::NoSheet => write!(f, "the workbook has no worksheet"),
+            XlsxError::MissingColumn(name) => write!(f, "missing column '{}'", name),
+            XlsxError::Store(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for XlsxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XlsxError::Read(e) => Some(e),
+            XlsxError::Write(e) => Some(e),
+            XlsxError::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<calamine::XlsxError> for XlsxError {
+    fn from(e: calamine::XlsxError) -> Self {
+        XlsxError::Read(e)
+    }
+}
+
+impl From<WriteError> for XlsxError {
+    fn from(e: WriteError) -> Self {
+        XlsxError::Write(e)
+    }
+}
+
+impl From<Error> for XlsxError {
+    fn from(e: Error) -> Self {
+        XlsxError::Store(e)
+    }
+}
+
+impl MovieManager {
+    /// 把所有电影按ID顺序导出为 xlsx 文件，只包含 `fields` 中的列。
+    pub fn export_xlsx(&self, path: &str, fields: &[Field]) -> Result<(), XlsxError> {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        let header = Format::new().set_bold();
+        let date = Format::new().set_num_format("yyyy-mm-dd");
+
+        for (col, field) in fields.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, field.name(), &header)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+
+        let records = self.search_fields(&MovieFilter::default(), fields)?;
+        for (i, record) in records.iter().enumerate() {
+            let row = i as u32 + 1;
+            for (col, field) in fields.iter().enumerate() {
+                let col = col as u16;
+                match (field, record.get(*field).unwrap_or_default()) {
+                    (Field::Id, _) => {
+                        sheet.write_number(row, col, record.id.unwrap_or_default())?
+                    }
+                    // 无法识别的日期（例如旧数据）原样写成文本
+                    (Field::ReleaseDate, text) => match release_date_cell(&text) {
+                        Some(value) => sheet.write_datetime_with_format(row, col, &value, &date)?,
+                        None => sheet.write_string(row, col, &text)?,
+                    },
+                    (_, text) => sheet.write_string(row, col, &text)?,
+                };
+            }
+        }
+        sheet.autofit();
+        workbook.save(path)?;
+        Ok(())
+    }
+}
+
+fn release_date_cell(release_date: &str) -> Option<ExcelDateTime> {
+    let (year, month, day) = parse_release_date(release_date)?;
+    ExcelDateTime::from_ymd(
+        u16::try_from(year).ok()?,
+        u8::try_from(month).ok()?,
+        u8::try_from(day).ok()?,
+    )
+    .ok()
+}
+
+/// 从 xlsx 的第一个工作表读取电影并写入存储。
+pub fn import_xlsx<S: MovieStore, R: Read + Seek>(
+    store: &S,
+    reader: R,
+) -> Result<ImportReport, XlsxError> {
+    let mut workbook: Xlsx<_> = Xlsx::new(reader)?;
+    let range = workbook.worksheet_range_at(0).ok_or(XlsxError::NoSheet)??;
+    let mut rows = range.rows();
+
+    let header: Vec<String> = rows
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|cell| cell.to_string().trim().to_lowercase().replace(' ', "_"))
+        .collect();
+    let mut index = [0; 4];
+    for (slot, name) in index.iter_mut().zip(COLUMNS) {
+        *slot = header
+            .iter()
+            .position(|h| h == name)
+            .ok_or(XlsxError::MissingColumn(name))?;
+    }
+
+    // 使用范围不一定从 A1 开始（例如上方有空行），表头所在的行号（从 0 开始）取自范围的起点
+    let header_row = range.start().map_or(0, |(row, _)| u64::from(row));
+    let mut report = ImportReport::default();
+    for (i, row) in rows.enumerate() {
+        if row.iter().all(|cell| cell.is_empty()) {
+            continue;
+        }
+        let cell = |col: usize| row.get(index[col]).unwrap_or(&Data::Empty);
+        let movie = parse_id(cell(0)).map(|id| {
+            Movie::new(
+                id,
+                cell(1).to_string(),
+                cell(2).to_string(),
+                release_date_text(cell(3)),
+            )
+        });
+        // 报告中的行号从 1 开始，与 Excel 中显示的一致
+        report.import_row(store, header_row + i as u64 + 2, movie);
+    }
+    Ok(report)
+}
+
+fn parse_id(cell: &Data) -> Result<u32, String> {
+    let id = match cell {
+        Data::Int(n) => u32::try_from(*n).ok(),
+        Data::Float(f) if f.fract() == 0.0 && *f >= 0.0 && *f <= u32::MAX as f64 => Some(*f as u32),
+        Data::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    id.ok_or_else(|| format!("invalid id '{}'", cell))
+}
+
+/// 日期单元格转换为 `年/月/日`，其他单元格按文本处理。
+fn release_date_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(_) | Data::DateTimeIso(_) => {
+            match cell
+                .as_date()
+                .and_then(|d| parse_release_date(&d.to_string()))
+            {
+                Some((year, month, day)) => format!("{}/{}/{}", year, month, day),
+                None => cell.to_string(),
+            }
+        }
+        _ => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = MovieManager::new(dir.path().join("a.db").to_str().unwrap()).unwrap();
+        source
+            .create_movie(
+                1,
+                "Inception".into(),
+                "Christopher Nolan".into(),
+                "2010/7/16".into(),
+            )
+            .unwrap();
+        source
+            .create_movie(
+                2,
+                "Tenet".into(),
+                "Christopher Nolan".into(),
+                "2020/8/26".into(),
+            )
+            .unwrap();
+        let path = dir.path().join("movies.xlsx");
+        source
+            .export_xlsx(path.to_str().unwrap(), &Field::ALL)
+            .unwrap();
+
+        let mut workbook: Xlsx<_> = calamine::open_workbook(&path).unwrap();
+        let range = workbook.worksheet_range_at(0).unwrap().unwrap();
+        assert_eq!(
+            range.get((0, 3)),
+            Some(&Data::String("release_date".into()))
+        );
+        assert!(matches!(range.get((1, 3)), Some(Data::DateTime(_))));
+
+        // 已存在的ID记为失败，其余的导入成功
+        let target = MovieManager::new(dir.path().join("b.db").to_str().unwrap()).unwrap();
+        target
+            .create_movie(2, "Old".into(), "Someone".into(), "2000/1/1".into())
+            .unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let report = import_xlsx(&target, file).unwrap();
+        assert_eq!(report.imported, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, 3);
+        assert_eq!(target.read_movie(1).unwrap(), source.read_movie(1).unwrap());
+
+        // 列按表头名称匹配，顺序和多余的列不影响；无效日期与手动创建一样被拒绝
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        for (col, name) in ["Notes", "Release Date", "Title", "ID", "Director"]
+            .iter()
+            .enumerate()
+        {
+            sheet.write_string(0, col as u16, *name).unwrap();
+        }
+        for (col, value) in ["", "2021-10-22", "Dune", "3", "Denis Villeneuve"]
+            .iter()
+            .enumerate()
+        {
+            sheet.write_string(1, col as u16, *value).unwrap();
+        }
+        for (col, value) in ["", "banana", "Bad", "4", "Someone"].iter().enumerate() {
+            sheet.write_string(2, col as u16, *value).unwrap();
+        }
+        let buffer = workbook.save_to_buffer().unwrap();
+        let report = import_xlsx(&target, std::io::Cursor::new(buffer)).unwrap();
+        assert_eq!(report.imported, 1);
+        assert_eq!(report.failures[0].0, 3);
+        assert_eq!(target.read_movie(3).unwrap().unwrap().title, "Dune");
+
+        // 表格从第 3 行开始时，报告的行号仍与 Excel 中显示的一致
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        for (col, name) in COLUMNS.iter().enumerate() {
+            sheet.write_string(2, col as u16 + 1, *name).unwrap();
+        }
+        for (col, value) in ["5", "Bad", "Someone", "banana"].iter().enumerate() {
+            sheet.write_string(3, col as u16 + 1, *value).unwrap();
+        }
+        let buffer = workbook.save_to_buffer().unwrap();
+        let report = import_xlsx(&target, std::io::Cursor::new(buffer)).unwrap();
+        assert_eq!(report.failures[0].0, 4);
+    }
+}